@@ -0,0 +1,164 @@
+//! settings of the players (search depth, learning parameters, network sizes, ..), loadable from TOML or JSON files
+#![allow(dead_code)]
+
+extern crate rustc_serialize;
+extern crate toml;
+
+use self::rustc_serialize::json::Json;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::str::FromStr;
+use super::PlayerType;
+use super::error::Error;
+use super::player::minimax_player::MinimaxConfig;
+use super::player::ai_value_player::AIValueConfig;
+use super::player::ai_q_player::AIQConfig;
+use super::player::ai_qoff_player::AIQOffConfig;
+use super::player::mcts_player::MCTSConfig;
+use super::player::alpha_zero_player::AlphaZeroConfig;
+use super::player::solver_player::SolverConfig;
+
+
+/// settings of all player types, every type uses its own section
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config
+{
+	pub minimax: MinimaxConfig,
+	pub aivalue: AIValueConfig, //also the fixed version
+	pub aiq: AIQConfig, //also the fixed and playing versions
+	pub aiqoff: AIQOffConfig,
+	pub mcts: MCTSConfig, //both rollout types
+	pub alphazero: AlphaZeroConfig,
+	pub solver: SolverConfig,
+}
+
+impl Config
+{
+	/// reads the settings of a TOML (.toml) or JSON file with a table for every section, like
+	/// [minimax] depth = 8 or {"minimax": {"depth": 8}}. settings not in the file keep their defaults
+	pub fn load(filename:&str) -> Result<Config, Error>
+	{
+		let mut text = String::new();
+		File::open(filename)?.read_to_string(&mut text)?;
+		let settings = if filename.ends_with(".toml") { Config::parse_toml(&text) } else { Config::parse_json(&text) }
+			.map_err(|err| Error::InvalidConfig(format!("{}: {}", filename, err)))?;
+		
+		let mut config = Config::default();
+		for (section, key, value) in settings
+		{
+			config.set(&section, &key, &value)?;
+		}
+		Ok(config)
+	}
+	
+	/// (section, key, value) of every setting in a TOML text
+	fn parse_toml(text:&str) -> Result<Vec<(String, String, String)>, String>
+	{
+		let mut parser = toml::Parser::new(text);
+		let table = match parser.parse()
+			{
+				Some(table) => table,
+				None => return Err(parser.errors.iter()
+						.map(|err| { let (line, col) = parser.to_linecol(err.lo); format!("line {}:{}: {}", line+1, col+1, err.desc) })
+						.collect::<Vec<String>>().join(", ")),
+			};
+		let mut settings = Vec::new();
+		for (section, values) in table.iter()
+		{
+			let values = values.as_table().ok_or_else(|| format!("\"{}\" has to be a table of settings", section))?;
+			for (key, value) in values.iter()
+			{
+				let value = match *value { toml::Value::String(ref text) => text.clone(), ref value => value.to_string() };
+				settings.push((section.clone(), key.clone(), value));
+			}
+		}
+		Ok(settings)
+	}
+	
+	/// (section, key, value) of every setting in a JSON text
+	fn parse_json(text:&str) -> Result<Vec<(String, String, String)>, String>
+	{
+		let json = Json::from_str(text).map_err(|err| err.to_string())?;
+		let table = json.as_object().ok_or_else(|| "expected an object of sections".to_string())?;
+		let mut settings = Vec::new();
+		for (section, values) in table.iter()
+		{
+			let values = values.as_object().ok_or_else(|| format!("\"{}\" has to be an object of settings", section))?;
+			for (key, value) in values.iter()
+			{
+				let value = match *value { Json::String(ref text) => text.clone(), ref value => value.to_string() };
+				settings.push((section.clone(), key.clone(), value));
+			}
+		}
+		Ok(settings)
+	}
+	
+	/// name of the section with the settings of the player type, None if it has no settings
+	pub fn section(p:PlayerType) -> Option<&'static str>
+	{
+		match p
+		{
+			PlayerType::Minimax => Some("minimax"),
+			PlayerType::AIValue | PlayerType::AIValueFixed => Some("aivalue"),
+			PlayerType::AIQ | PlayerType::AIQFixed | PlayerType::AIQPlay => Some("aiq"),
+			PlayerType::AIQOff | PlayerType::AIQOffFixed => Some("aiqoff"),
+			PlayerType::MCTS | PlayerType::MCTSRandom => Some("mcts"),
+			PlayerType::AlphaZero | PlayerType::AlphaZeroFixed => Some("alphazero"),
+			PlayerType::Solver => Some("solver"),
+			PlayerType::None | PlayerType::IO | PlayerType::Random => None,
+		}
+	}
+	
+	/// changes the setting key of a section to the given value (as text, lists like 126,42)
+	pub fn set(&mut self, section:&str, key:&str, value:&str) -> Result<(), Error>
+	{
+		let key = &key.trim().to_lowercase()[..];
+		let result = match section
+		{
+			"minimax" => self.minimax.set(key, value),
+			"aivalue" => self.aivalue.set(key, value),
+			"aiq" => self.aiq.set(key, value),
+			"aiqoff" => self.aiqoff.set(key, value),
+			"mcts" => self.mcts.set(key, value),
+			"alphazero" => self.alphazero.set(key, value),
+			"solver" => self.solver.set(key, value),
+			_ => return Err(Error::InvalidConfig(format!("unknown section \"{}\"", section))),
+		};
+		result.map_err(|err| match err
+			{
+				Error::InvalidConfig(reason) => Error::InvalidConfig(format!("{}: {}", section, reason)),
+				err => err,
+			})
+	}
+	
+	/// changes a setting of the section of player type p
+	pub fn set_for(&mut self, p:PlayerType, key:&str, value:&str) -> Result<(), Error>
+	{
+		match Config::section(p)
+		{
+			Some(section) => self.set(section, key, value),
+			None => Err(Error::InvalidConfig(format!("{:?} has no settings", p))),
+		}
+	}
+}
+
+/// parses the value of setting key
+pub fn parse_value<T:FromStr>(key:&str, value:&str) -> Result<T, Error>
+	where T::Err: fmt::Display
+{
+	value.trim().parse::<T>().map_err(|err| Error::InvalidConfig(format!("invalid value \"{}\" for \"{}\": {}", value, key, err)))
+}
+
+/// parses a list of numbers like 126,42 or [126, 42]
+pub fn parse_list<T:FromStr>(key:&str, value:&str) -> Result<Vec<T>, Error>
+	where T::Err: fmt::Display
+{
+	let value = value.trim().trim_matches(|c| c == '[' || c == ']');
+	value.split(',').filter(|item| !item.trim().is_empty()).map(|item| parse_value(key, item)).collect()
+}
+
+pub fn unknown_setting(key:&str) -> Error
+{
+	Error::InvalidConfig(format!("unknown setting \"{}\"", key))
+}
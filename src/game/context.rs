@@ -0,0 +1,56 @@
+//! what the players know about the game besides the position: history, clocks and the match they play in
+#![allow(dead_code)]
+
+use super::field::{Field, PlayerId};
+use std::time::Duration;
+
+
+/// state of the game and match, given to the players with the field
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameContext
+{
+	pub game: u32, //index of the current game in the match, starting at 0
+	pub games: u32, //number of games of the match
+	pub start_player: PlayerId,
+	pub history: Vec<u32>, //columns (starting at 0) played in this game, including the opening
+	pub players: [String; 2], //names of player X and O, like Minimax(depth=8), empty if not set yet
+	pub time_used: [Duration; 2], //thinking time of X and O in this game
+	pub game_time: Option<Duration>, //thinking time of every player per game, None without clock
+	pub training: bool, //the games are played to train the learning players, false when evaluating
+}
+
+impl GameContext
+{
+	/// context of a single game in the position of the field
+	pub fn new(field:&Field) -> GameContext
+	{
+		GameContext { game: 0, games: 1, start_player: field.get_first_player(), history: field.get_turns().iter().map(|&(x, _)| x).collect(),
+			players: [String::new(), String::new()], time_used: [Duration::new(0, 0); 2], game_time: None, training: false }
+	}
+	
+	/// name of the opponent of player p, empty if it is not set yet
+	pub fn opponent(&self, p:PlayerId) -> &str
+	{
+		&self.players[p.other().index()]
+	}
+	
+	/// thinking time player p has left in this game, None without clock
+	pub fn remaining(&self, p:PlayerId) -> Option<Duration>
+	{
+		self.game_time.map(|time| time.checked_sub(self.time_used[p.index()]).unwrap_or(Duration::new(0, 0)))
+	}
+	
+	/// time player p may use for its next move: its limit per move (None is unlimited), shortened to an even share of the
+	/// remaining game time for its moves left in the field
+	pub fn time_for_move(&self, field:&Field, p:PlayerId, limit:Option<Duration>) -> Option<Duration>
+	{
+		let moves = (field.get_size() - field.get_turns().len() as u32).div_ceil(2);
+		let share = self.remaining(p).map(|time| time / moves.max(1));
+		match (limit, share)
+		{
+			(Some(limit), Some(share)) => Some(limit.min(share)),
+			(limit, None) => limit,
+			(None, share) => share,
+		}
+	}
+}
@@ -0,0 +1,78 @@
+//! error type of fields, games and players
+#![allow(dead_code)]
+
+use std::error;
+use std::fmt;
+use std::io;
+use super::field::PositionError;
+
+
+#[derive(Debug)]
+pub enum Error
+{
+	IllegalMove(u32), //column outside of the field
+	ColumnFull(u32), //column, that has no free square left
+	GameOver, //move after the game has ended
+	NotInitialized, //player was used before init
+	NoPlayer, //game was started without both players set
+	UnsupportedRules(u32, u32, u32), //width, height and win length, that can not be played
+	InvalidConfig(String), //description of the invalid player setting
+	InvalidArgument(String), //description of the invalid argument of a game function
+	PlayerInit(String, Box<Error>), //player type, cause
+	CorruptModel(String, String), //file name, what is wrong with it
+	Position(PositionError),
+	Io(io::Error),
+	Interrupted, //playing was stopped by the interrupt flag of the game
+}
+
+impl fmt::Display for Error
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			Error::IllegalMove(x) => write!(f, "illegal move: column {} is not on the field", x),
+			Error::ColumnFull(x) => write!(f, "illegal move: column {} is full", x),
+			Error::GameOver => write!(f, "illegal move: the game is already over"),
+			Error::NotInitialized => write!(f, "player was not initialized"),
+			Error::NoPlayer => write!(f, "both players have to be set before playing"),
+			Error::UnsupportedRules(w, h, win) => write!(f, "connect {} on a {}x{} board is not supported (at least 2 columns and rows, at most 64 squares and room for a winning row)", win, w, h),
+			Error::InvalidConfig(ref reason) => write!(f, "invalid player configuration: {}", reason),
+			Error::InvalidArgument(ref reason) => write!(f, "invalid argument: {}", reason),
+			Error::PlayerInit(ref name, ref cause) => write!(f, "player {} could not be initialized: {}", name, cause),
+			Error::CorruptModel(ref file, ref reason) => write!(f, "model file {} is corrupt: {}", file, reason),
+			Error::Position(ref err) => write!(f, "invalid position: {}", err),
+			Error::Io(ref err) => write!(f, "I/O error: {}", err),
+			Error::Interrupted => write!(f, "interrupted"),
+		}
+	}
+}
+
+impl error::Error for Error
+{
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		match *self
+		{
+			Error::PlayerInit(_, ref cause) => Some(&**cause),
+			Error::Io(ref err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for Error
+{
+	fn from(err:io::Error) -> Error
+	{
+		Error::Io(err)
+	}
+}
+
+impl From<PositionError> for Error
+{
+	fn from(err:PositionError) -> Error
+	{
+		Error::Position(err)
+	}
+}
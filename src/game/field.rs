@@ -1,20 +1,208 @@
 #![allow(dead_code)]
 
+use std::fmt;
+use std::char;
+use std::collections::HashSet;
+use super::error::Error;
+
+const MAX_SIZE:u32 = 64; //number of bits in a bitboard
+pub const DEFAULT_WIDTH:u32 = 7;
+pub const DEFAULT_HEIGHT:u32 = 6;
+pub const DEFAULT_WIN:u32 = 4; //classic connect four
+
+
+/// one of the two players, player 1 (X) or player 2 (O)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlayerId
+{
+	P1,
+	P2,
+}
+
+impl PlayerId
+{
+	pub fn other(self) -> PlayerId
+	{
+		match self
+		{
+			PlayerId::P1 => PlayerId::P2,
+			PlayerId::P2 => PlayerId::P1,
+		}
+	}
+	
+	/// 0 for player 1, 1 for player 2
+	pub fn index(self) -> usize
+	{
+		match self
+		{
+			PlayerId::P1 => 0,
+			PlayerId::P2 => 1,
+		}
+	}
+	
+	fn from_index(i:usize) -> PlayerId
+	{
+		if i == 0 { PlayerId::P1 } else { PlayerId::P2 }
+	}
+}
+
+impl fmt::Display for PlayerId
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			PlayerId::P1 => write!(f, "X"),
+			PlayerId::P2 => write!(f, "O"),
+		}
+	}
+}
+
+/// content of a square on the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell
+{
+	Empty,
+	Stone(PlayerId),
+}
+
+/// state of the game on a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState
+{
+	Running,
+	Draw,
+	Win(PlayerId),
+}
+
+impl GameState
+{
+	pub fn is_over(self) -> bool
+	{
+		self != GameState::Running
+	}
+	
+	pub fn winner(self) -> Option<PlayerId>
+	{
+		match self
+		{
+			GameState::Win(p) => Some(p),
+			_ => None,
+		}
+	}
+}
+
+/// reasons why a position could not be set up
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError
+{
+	InvalidColumn(usize, char), //index in the move string, character that is no column
+	ColumnFull(usize, u32), //index in the move string, (0-based) column that is full or outside the board
+	GameOver(usize), //index in the move string, game already ended before this move
+	InvalidCell(usize, char), //row in the diagram, character that is no cell
+	WrongSize(u32, u32), //width and height of a diagram, that does not fit the field
+	FloatingStone(u32, u32), //(0-based) column and row of a stone above an empty square
+	StoneCount(u32, u32), //number of stones of player 1 and 2, that can not occur by alternating moves
+	TwoWinners, //both players have a winning row
+	Unreachable, //no order of moves leads to this position
+}
+
+impl fmt::Display for PositionError
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			PositionError::InvalidColumn(i, c) => write!(f, "invalid column '{}' at move {}", c, i+1),
+			PositionError::ColumnFull(i, x) => write!(f, "column {} can not be played at move {}", x+1, i+1),
+			PositionError::GameOver(i) => write!(f, "game is already over at move {}", i+1),
+			PositionError::InvalidCell(y, c) => write!(f, "invalid cell '{}' in row {}", c, y+1),
+			PositionError::WrongSize(w, h) => write!(f, "board size {}x{} is not supported here", w, h),
+			PositionError::FloatingStone(x, y) => write!(f, "stone in column {}, row {} is floating", x+1, y+1),
+			PositionError::StoneCount(n1, n2) => write!(f, "{} X stones and {} O stones can not occur", n1, n2),
+			PositionError::TwoWinners => write!(f, "both players have a winning row"),
+			PositionError::Unreachable => write!(f, "position can not be reached by a game"),
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct Field
 {
-	field: Vec<i32>,
 	w: u32,
 	h: u32,
+	win: u32, //number of stones in a row needed to win
+	stones: [u64; 2], //bitboards of player 1 and player 2 stones, bit index y*w+x
+	heights: Vec<u32>, //number of stones in every column
+	win_masks: Vec<(u32, u64)>, //(shift, possible start positions) for every direction a row can go
 	turns: Vec<(u32, u32)>,
+	state: GameState, //cached game state, updated with every move
+	prev_states: Vec<GameState>, //game states before every turn to restore them on undo
 }
 
 impl Field
 {
-	pub fn new(width:u32, height:u32) -> Field
+	pub fn new(width:u32, height:u32, win:u32) -> Field
+	{
+		assert!(width > 0 && height > 0 && width*height <= MAX_SIZE, "Field size has to fit into a 64 bit board!");
+		assert!(win > 0, "Win length has to be positive!");
+		Field { w:width, h:height, win, stones:[0u64; 2], heights:vec![0u32; width as usize],
+			win_masks:Field::create_win_masks(width, height, win), turns: Vec::new(), state: GameState::Running, prev_states: Vec::new() }
+	}
+	
+	/// sets up a position on the standard board from a move sequence, see play_moves
+	pub fn from_moves(moves:&str) -> Result<Field, PositionError>
 	{
-		let size:usize = (width*height) as usize;
-		Field { field:vec![0i32; size], w:width, h:height, turns: Vec::new() }
+		let mut field = Field::new(DEFAULT_WIDTH, DEFAULT_HEIGHT, DEFAULT_WIN);
+		field.play_moves(moves)?;
+		Ok(field)
+	}
+	
+	/// sets up a position from a board diagram, see load_diagram. the board size is taken from the diagram
+	pub fn from_diagram(diagram:&str) -> Result<Field, PositionError>
+	{
+		let rows = Field::parse_diagram(diagram)?;
+		let (w, h) = (rows.first().map_or(0, |row| row.len()) as u32, rows.len() as u32);
+		if !Field::is_supported(w, h, DEFAULT_WIN) { return Err(PositionError::WrongSize(w, h)); }
+		let mut field = Field::new(w, h, DEFAULT_WIN);
+		field.load_diagram(diagram)?;
+		Ok(field)
+	}
+	
+	/// checks if a field with these rules can be created and played on by all players: fits into the bitboards and a row can be built
+	pub fn is_supported(width:u32, height:u32, win:u32) -> bool
+	{
+		width >= 2 && height >= 2 && width*height <= MAX_SIZE && win >= 2 && (win <= width || win <= height)
+	}
+	
+	fn create_win_masks(w:u32, h:u32, win:u32) -> Vec<(u32, u64)>
+	{
+		//directions: right, down, down-right, down-left (as bit shifts in a row-major board)
+		let len = win as i32 - 1;
+		let dirs:[(i32, i32, u32); 4] = [(1, 0, 1), (0, 1, w), (1, 1, w+1), (-1, 1, w-1)];
+		let mut masks = Vec::with_capacity(dirs.len());
+		for &(dx, dy, shift) in dirs.iter()
+		{
+			let mut mask = 0u64;
+			for y in 0..h as i32
+			{
+				for x in 0..w as i32
+				{
+					let (ex, ey) = (x + len*dx, y + len*dy); //end of the row starting here
+					if ex >= 0 && ex < w as i32 && ey < h as i32
+					{
+						mask |= 1u64 << (y as u32*w + x as u32);
+					}
+				}
+			}
+			if mask != 0 { masks.push((shift, mask)); }
+		}
+		masks
+	}
+	
+	fn shr(bits:u64, n:u32) -> u64
+	{
+		bits.checked_shr(n).unwrap_or(0)
 	}
 	
 	pub fn get_w(&self) -> u32
@@ -29,12 +217,30 @@ impl Field
 	
 	pub fn get_size(&self) -> u32
 	{
-		self.w*self.h //self.field.len()
+		self.w*self.h
 	}
 	
-	pub fn get_field(&self) -> &Vec<i32>
+	pub fn get_win(&self) -> u32
 	{
-		&self.field
+		self.win
+	}
+	
+	/// short description of the rules, e.g. for file names: "7x6" for connect four, "9x7-5" for other win lengths
+	pub fn get_rules_tag(&self) -> String
+	{
+		if self.win == DEFAULT_WIN { format!("{}x{}", self.w, self.h) }
+		else { format!("{}x{}-{}", self.w, self.h, self.win) }
+	}
+	
+	/// cells in row-major order, as used for the NN inputs
+	pub fn get_field(&self) -> Vec<Cell>
+	{
+		let mut field = Vec::with_capacity(self.get_size() as usize);
+		for i in 0..self.get_size()
+		{
+			field.push(self.get_bit(i));
+		}
+		field
 	}
 	
 	pub fn get_turns(&self) -> &Vec<(u32, u32)>
@@ -42,52 +248,245 @@ impl Field
 		&self.turns
 	}
 	
-	pub fn get_val(&self, x:u32, y:u32) -> i32
+	fn get_bit(&self, i:u32) -> Cell
+	{
+		let bit = 1u64 << i;
+		if self.stones[0] & bit != 0 { Cell::Stone(PlayerId::P1) }
+		else if self.stones[1] & bit != 0 { Cell::Stone(PlayerId::P2) }
+		else { Cell::Empty }
+	}
+	
+	pub fn get_val(&self, x:u32, y:u32) -> Cell
 	{
 		if x<self.w && y<self.h
 		{
-			self.field[(y*self.w+x) as usize]
+			self.get_bit(y*self.w+x)
 		}
 		else
 		{
-			0
+			Cell::Empty
 		}
 	}
 	
-	fn set_val(&mut self, x:u32, y:u32, val:i32)
+	pub fn reset(&mut self)
 	{
-		if x<self.w && y<self.h
+		self.stones = [0u64; 2];
+		for height in self.heights.iter_mut()
 		{
-			self.field[(y*self.w+x) as usize] = val;
+			*height = 0;
 		}
+		self.turns.clear();
+		self.state = GameState::Running;
+		self.prev_states.clear();
 	}
 	
-	pub fn reset(&mut self)
+	pub fn play(&mut self, player:PlayerId, x:u32) -> Result<(), Error>
 	{
-		for i in 0..self.field.len()
+		if x >= self.w { return Err(Error::IllegalMove(x)); }
+		if !self.is_valid_play(x) { return Err(Error::ColumnFull(x)); }
+		if self.state.is_over() { return Err(Error::GameOver); }
+		
+		let y = self.h - 1 - self.heights[x as usize];
+		self.stones[player.index()] |= 1u64 << (y*self.w+x);
+		self.heights[x as usize] += 1;
+		self.turns.push((x, y));
+		
+		//only the new stone can create a new row
+		self.prev_states.push(self.state);
+		if self.is_winning_stone(x, y) { self.state = GameState::Win(player); }
+		else if self.is_full() { self.state = GameState::Draw; }
+		Ok(())
+	}
+	
+	/// player, whose turn it is (player 1 on an empty field, else the other player than the one who played last)
+	pub fn get_player_to_move(&self) -> PlayerId
+	{
+		match self.turns.last()
 		{
-			self.field[i] = 0;
+			Some(&(x, y)) => if self.get_val(x, y) == Cell::Stone(PlayerId::P1) { PlayerId::P2 } else { PlayerId::P1 },
+			None => PlayerId::P1,
+		}
+	}
+	
+	/// player who began the game (owner of the first stone), the player to move on an empty field
+	pub fn get_first_player(&self) -> PlayerId
+	{
+		match self.turns.first()
+		{
+			Some(&(x, y)) => if self.get_val(x, y) == Cell::Stone(PlayerId::P1) { PlayerId::P1 } else { PlayerId::P2 },
+			None => self.get_player_to_move(),
 		}
-		self.turns.clear();
 	}
 	
-	pub fn play(&mut self, player:i32, x:u32) -> bool
+	/// plays a sequence of 1-based columns ("4453"), as used by connect four solvers and their test sets.
+	/// players alternate, starting with the player to move. columns above 9 are written as letters (a = 10).
+	/// whitespace is ignored. on error, the field is left unchanged.
+	pub fn play_moves(&mut self, moves:&str) -> Result<(), PositionError>
 	{
-		if x >= self.w || player < 1 || player > 2 || !self.is_valid_play(x)
+		let p = self.get_player_to_move();
+		self.play_moves_from(p, moves)
+	}
+	
+	/// plays a move sequence like play_moves, but the first move is done by the given player
+	pub fn play_moves_from(&mut self, first:PlayerId, moves:&str) -> Result<(), PositionError>
+	{
+		let mut p = first;
+		let mut played = 0;
+		let mut result = Ok(());
+		for (i, c) in moves.chars().filter(|c| !c.is_whitespace()).enumerate()
+		{
+			let x = match c.to_digit(36)
+				{
+					Some(x) if x > 0 => x - 1,
+					_ => { result = Err(PositionError::InvalidColumn(i, c)); break; },
+				};
+			if self.get_state().is_over() { result = Err(PositionError::GameOver(i)); break; }
+			if self.play(p, x).is_err() { result = Err(PositionError::ColumnFull(i, x)); break; }
+			p = p.other();
+			played += 1;
+		}
+		
+		if result.is_err()
+		{
+			for _ in 0..played
+			{
+				self.undo();
+			}
+		}
+		result
+	}
+	
+	/// move sequence of the current position (1-based columns), inverse of play_moves
+	pub fn to_moves(&self) -> String
+	{
+		self.turns.iter().map(|&(x, _)| char::from_digit(x+1, 36).unwrap_or('?')).collect()
+	}
+	
+	/// board diagram of the position: one line per row from top to bottom, X = player 1, O = player 2, . = empty
+	pub fn to_diagram(&self) -> String
+	{
+		let mut rows = Vec::with_capacity(self.h as usize);
+		for y in 0..self.h
+		{
+			let row:String = (0..self.w).map(|x| match self.get_val(x, y)
+					{
+						Cell::Stone(PlayerId::P1) => 'X',
+						Cell::Stone(PlayerId::P2) => 'O',
+						Cell::Empty => '.',
+					}).collect();
+			rows.push(row);
+		}
+		rows.join("\n")
+	}
+	
+	/// cells of the diagram rows. spaces and | between cells and separator lines of dashes are ignored,
+	/// so the output of print can be read as well
+	fn parse_diagram(diagram:&str) -> Result<Vec<Vec<Cell>>, PositionError>
+	{
+		let mut rows = Vec::new();
+		for line in diagram.lines().filter(|line| line.chars().any(|c| !c.is_whitespace() && c != '-'))
+		{
+			let mut row = Vec::new();
+			for c in line.chars().filter(|c| !c.is_whitespace() && *c != '|')
+			{
+				row.push(match c
+					{
+						'X' | 'x' => Cell::Stone(PlayerId::P1),
+						'O' | 'o' => Cell::Stone(PlayerId::P2),
+						'.' => Cell::Empty,
+						_ => return Err(PositionError::InvalidCell(rows.len(), c)),
+					});
+			}
+			rows.push(row);
+		}
+		Ok(rows)
+	}
+	
+	/// replaces the position by the one in the diagram (see to_diagram), which has to be of the same size.
+	/// the diagram is checked to be reachable and a possible move order is restored, so undo keeps working.
+	/// if both players have the same number of stones, player 1 is assumed to have started if that is possible.
+	pub fn load_diagram(&mut self, diagram:&str) -> Result<(), PositionError>
+	{
+		let rows = Field::parse_diagram(diagram)?;
+		if rows.len() as u32 != self.h || rows.iter().any(|row| row.len() as u32 != self.w)
+		{
+			return Err(PositionError::WrongSize(rows.first().map_or(0, |row| row.len()) as u32, rows.len() as u32));
+		}
+		
+		//build bitboards and check gravity
+		let mut stones = [0u64; 2];
+		let mut heights = vec![0u32; self.w as usize];
+		for x in 0..self.w
 		{
-			return false;
+			for y in (0..self.h).rev()
+			{
+				let p = match rows[y as usize][x as usize]
+					{
+						Cell::Stone(p) => p,
+						Cell::Empty => continue,
+					};
+				if y + heights[x as usize] + 1 != self.h { return Err(PositionError::FloatingStone(x, y)); }
+				stones[p.index()] |= 1u64 << (y*self.w+x);
+				heights[x as usize] += 1;
+			}
 		}
 		
-		for y in (0..self.h).rev()
+		//check stone count and winners to find out who played last
+		let (n1, n2) = (stones[0].count_ones(), stones[1].count_ones());
+		if n1 > n2 + 1 || n2 > n1 + 1 { return Err(PositionError::StoneCount(n1, n2)); }
+		let (won1, won2) = (self.has_won(stones[0]), self.has_won(stones[1]));
+		if won1 && won2 { return Err(PositionError::TwoWinners); }
+		let mut last = if n1 > n2 || (n1 == n2 && won1) { 0 } else { 1 }; //index of the player, that played last
+		if (won1 && last != 0) || (won2 && last != 1) { return Err(PositionError::Unreachable); }
+		
+		//take back stones to find a move order (with equal stones and no winner, both players could have started)
+		let mut order = Vec::with_capacity((n1 + n2) as usize);
+		if !self.find_move_order(&mut stones, &mut heights, last, &mut order, &mut HashSet::new())
 		{
-			if self.get_val(x, y) == 0
+			if n1 != n2 || won1 || won2 { return Err(PositionError::Unreachable); }
+			last = 0;
+			if !self.find_move_order(&mut stones, &mut heights, last, &mut order, &mut HashSet::new())
 			{
-				self.set_val(x, y, player);
-				self.turns.push((x, y));
-				return true;
+				return Err(PositionError::Unreachable);
 			}
 		}
 		
+		//replay the moves
+		self.reset();
+		let mut p = PlayerId::from_index(if (n1 + n2) % 2 == 1 { last } else { 1 - last }); //first player
+		for &x in order.iter().rev()
+		{
+			if self.play(p, x).is_err() { return Err(PositionError::Unreachable); }
+			p = p.other();
+		}
+		Ok(())
+	}
+	
+	/// takes back top stones of player p and the other player alternately, so that no winning row is left
+	/// in between (only the last move could have won). pushes the columns in reverse playing order.
+	fn find_move_order(&self, stones:&mut [u64; 2], heights:&mut Vec<u32>, p:usize, order:&mut Vec<u32>, visited:&mut HashSet<Vec<u32>>) -> bool
+	{
+		if heights.iter().all(|&height| height == 0) { return true; }
+		if !visited.insert(heights.clone()) { return false; } //the heights determine the remaining stones
+		
+		for x in 0..self.w
+		{
+			let height = heights[x as usize];
+			if height == 0 { continue; }
+			let bit = 1u64 << ((self.h - height)*self.w + x);
+			if stones[p] & bit == 0 { continue; }
+			
+			stones[p] &= !bit;
+			if !self.has_won(stones[0]) && !self.has_won(stones[1])
+			{
+				heights[x as usize] -= 1;
+				order.push(x);
+				if self.find_move_order(stones, heights, 1-p, order, visited) { return true; }
+				order.pop();
+				heights[x as usize] += 1;
+			}
+			stones[p] |= bit;
+		}
 		false
 	}
 	
@@ -95,7 +494,15 @@ impl Field
 	{
 		match self.turns.pop()
 		{
-			Some((x, y)) => { self.set_val(x, y, 0); true },
+			Some((x, y)) =>
+				{
+					let bit = !(1u64 << (y*self.w+x));
+					self.stones[0] &= bit;
+					self.stones[1] &= bit;
+					self.heights[x as usize] -= 1;
+					self.state = self.prev_states.pop().unwrap_or(GameState::Running);
+					true
+				},
 			_ => false
 		}
 	}
@@ -111,9 +518,9 @@ impl Field
 				//str += &(self.get_val(x, y).to_string()); //as number
 				str += match self.get_val(x, y)
 					{
-						1 => "X",
-						2 => "O",
-						_ => " ",
+						Cell::Stone(PlayerId::P1) => "X",
+						Cell::Stone(PlayerId::P2) => "O",
+						Cell::Empty => ".",
 					}; // as XO string
 				str += " | ";
 				strline += "----";
@@ -128,39 +535,374 @@ impl Field
 	
 	pub fn is_valid_play(&self, x:u32) -> bool
 	{
-		x < self.w && self.get_val(x, 0) == 0
+		x < self.w && self.heights[x as usize] < self.h
 	}
 	
 	pub fn is_full(&self) -> bool
 	{
-		self.turns.len() == self.field.len()
+		self.turns.len() as u32 == self.get_size()
+	}
+	
+	/// checks if the stone at (x, y) is part of a winning row, only looks at the rows through this stone
+	fn is_winning_stone(&self, x:u32, y:u32) -> bool
+	{
+		let player = self.get_val(x, y);
+		if player == Cell::Empty { return false; }
+		for &(dx, dy) in [(1i32, 0i32), (0, 1), (1, 1), (1, -1)].iter()
+		{
+			let mut count = 1;
+			for &dir in [1i32, -1].iter()
+			{
+				let (mut cx, mut cy) = (x as i32 + dir*dx, y as i32 + dir*dy);
+				while cx >= 0 && cy >= 0 && self.get_val(cx as u32, cy as u32) == player
+				{
+					count += 1;
+					cx += dir*dx;
+					cy += dir*dy;
+				}
+			}
+			if count >= self.win { return true; }
+		}
+		false
+	}
+	
+	/// hash of the position, equal for equal positions independent of the move order (keyed by the bitboards)
+	pub fn get_hash(&self) -> u64
+	{
+		Field::hash_stones(self.stones[0], self.stones[1])
+	}
+	
+	/// hash of the position mirrored at the central column
+	pub fn get_mirror_hash(&self) -> u64
+	{
+		Field::hash_stones(self.mirror_stones(self.stones[0]), self.mirror_stones(self.stones[1]))
+	}
+	
+	fn hash_stones(stones1:u64, stones2:u64) -> u64
+	{
+		Field::mix(stones1 ^ Field::mix(stones2 ^ 0x9e37_79b9_7f4a_7c15))
 	}
 	
-	/// return: 0=running, -1=draw, 1=player1 win, 2=player2 win
-	pub fn get_state(&self) -> i32
+	/// bit mixing function of splitmix64
+	fn mix(mut z:u64) -> u64
 	{
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+	
+	/// swaps the columns x and w-1-x of a bitboard
+	fn mirror_stones(&self, stones:u64) -> u64
+	{
+		let mut column = 0u64; //mask of the first column
 		for y in 0..self.h
 		{
-			for x in 0..self.w
+			column |= 1u64 << (y*self.w);
+		}
+		let mut mirrored = 0u64;
+		for x in 0..self.w
+		{
+			mirrored |= ((stones >> x) & column) << (self.w-1-x);
+		}
+		mirrored
+	}
+	
+	/// scans the whole board for a winning row of the given stones
+	fn has_won(&self, stones:u64) -> bool
+	{
+		for &(shift, mask) in self.win_masks.iter()
+		{
+			//a row starts at every bit, that has stones at the next win-1 positions in the direction
+			let mut rows = stones;
+			for i in 1..self.win
 			{
-				if self.get_val(x, y) == 0 { continue; }
-				
-				if (self.get_val(x, y) == self.get_val(x+1, y) && self.get_val(x+1, y) == self.get_val(x+2, y) && self.get_val(x+2, y) == self.get_val(x+3, y)) ||
-					(self.get_val(x, y) == self.get_val(x+1, y+1) && self.get_val(x+1, y+1) == self.get_val(x+2, y+2) && self.get_val(x+2, y+2) == self.get_val(x+3, y+3)) ||
-					(self.get_val(x, y) == self.get_val(x, y+1) && self.get_val(x, y+1) == self.get_val(x, y+2) && self.get_val(x, y+2) == self.get_val(x, y+3)) ||
-					(x >= 3 && self.get_val(x, y) == self.get_val(x-1, y+1) && self.get_val(x-1, y+1) == self.get_val(x-2, y+2) && self.get_val(x-2, y+2) == self.get_val(x-3, y+3))
+				rows &= Field::shr(stones, i*shift);
+			}
+			if rows & mask != 0 { return true; }
+		}
+		false
+	}
+	
+	pub fn get_state(&self) -> GameState
+	{
+		self.state
+	}
+	
+	/// computes the game state from scratch by scanning the whole board
+	fn scan_state(&self) -> GameState
+	{
+		if self.has_won(self.stones[0]) { return GameState::Win(PlayerId::P1); }
+		if self.has_won(self.stones[1]) { return GameState::Win(PlayerId::P2); }
+		if self.is_full() { return GameState::Draw; }
+		GameState::Running
+	}
+}
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{Field, PlayerId, Cell, GameState, PositionError};
+	
+	const DRAW:&str = "467542311242375735765675733135661624214421"; //full board without a winning row
+	
+	#[test]
+	fn move_strings_round_trip()
+	{
+		for &moves in ["", "4", "4453", "1234567", "444444", DRAW].iter()
+		{
+			let field = Field::from_moves(moves).unwrap();
+			assert_eq!(field.get_turns().len(), moves.len());
+			assert_eq!(field.to_moves(), moves);
+			assert_eq!(Field::from_moves(&field.to_moves()).unwrap().get_hash(), field.get_hash());
+		}
+		assert_eq!(Field::from_moves(" 44 53\n").unwrap().to_moves(), "4453"); //whitespace is ignored
+		
+		//players alternate, starting with the player to move
+		let mut field = Field::from_moves("4").unwrap();
+		field.play_moves("43").unwrap();
+		assert_eq!(field.get_val(3, 4), Cell::Stone(PlayerId::P2));
+		assert_eq!(field.get_val(2, 5), Cell::Stone(PlayerId::P1));
+		assert_eq!(field.get_player_to_move(), PlayerId::P2);
+	}
+	
+	#[test]
+	fn invalid_moves_leave_the_field_unchanged()
+	{
+		let mut field = Field::from_moves("44").unwrap();
+		let hash = field.get_hash();
+		assert_eq!(field.play_moves("53-1"), Err(PositionError::InvalidColumn(2, '-')));
+		assert_eq!(field.play_moves("0"), Err(PositionError::InvalidColumn(0, '0')));
+		assert_eq!(field.play_moves("8"), Err(PositionError::ColumnFull(0, 7)));
+		assert_eq!(field.play_moves("1444444"), Err(PositionError::ColumnFull(5, 3)));
+		assert_eq!(field.play_moves("12121212"), Err(PositionError::GameOver(7))); //X won with the 4th stone in column 1
+		assert_eq!(field.to_moves(), "44");
+		assert_eq!(field.get_hash(), hash);
+		assert_eq!(field.get_state(), GameState::Running);
+		assert_eq!(field.get_player_to_move(), PlayerId::P1);
+		
+		assert_eq!(Field::from_moves("12121212").err(), Some(PositionError::GameOver(7)));
+	}
+	
+	#[test]
+	fn letter_columns_on_wide_boards()
+	{
+		let mut field = Field::new(12, 5, 4);
+		field.play_moves("abc1A").unwrap();
+		let columns:Vec<u32> = field.get_turns().iter().map(|&(x, _)| x).collect();
+		assert_eq!(columns, vec![9, 10, 11, 0, 9]);
+		assert_eq!(field.to_moves(), "abc1a");
+		assert_eq!(field.play_moves("d"), Err(PositionError::ColumnFull(0, 12)));
+		assert_eq!(Field::from_moves("a").err(), Some(PositionError::ColumnFull(0, 9)));
+	}
+	
+	#[test]
+	fn known_results()
+	{
+		let results = [
+				("", GameState::Running),
+				("1212121", GameState::Win(PlayerId::P1)), //vertical
+				("4455667", GameState::Win(PlayerId::P1)), //horizontal
+				("12432434413", GameState::Win(PlayerId::P1)), //diagonal up
+				("12432131221", GameState::Win(PlayerId::P1)), //diagonal down
+				("2143334244", GameState::Win(PlayerId::P2)), //diagonal up
+				("1433121122", GameState::Win(PlayerId::P2)), //diagonal down
+				("7665545443", GameState::Win(PlayerId::P2)),
+				(DRAW, GameState::Draw),
+			];
+		for &(moves, state) in results.iter()
+		{
+			let field = Field::from_moves(moves).unwrap();
+			assert_eq!(field.get_state(), state, "{}", moves);
+			assert_eq!(field.scan_state(), state, "{}", moves);
+		}
+	}
+	
+	#[test]
+	fn diagrams_round_trip()
+	{
+		for &moves in ["", "4", "4453", "12432434413", DRAW].iter()
+		{
+			let field = Field::from_moves(moves).unwrap();
+			let mut loaded = Field::from_diagram(&field.to_diagram()).unwrap();
+			assert_eq!(loaded.to_diagram(), field.to_diagram());
+			assert_eq!(loaded.get_hash(), field.get_hash());
+			assert_eq!(loaded.get_state(), field.get_state());
+			assert_eq!(loaded.get_player_to_move(), field.get_player_to_move());
+			
+			//the restored move order can be taken back
+			assert_eq!(loaded.get_turns().len(), moves.len());
+			while loaded.undo() { assert_eq!(loaded.get_state(), loaded.scan_state()); }
+			assert_eq!(loaded.get_hash(), Field::new(7, 6, 4).get_hash());
+		}
+		
+		//the output of print with separators
+		let field = Field::from_diagram(" . | . | . | . |\n---------------\n . | O | . | . |\n---------------\n . | X | X | . |").unwrap();
+		assert_eq!(field.to_diagram(), "....\n.O..\n.XX.");
+		assert_eq!(field.get_player_to_move(), PlayerId::P2);
+	}
+	
+	#[test]
+	fn ambiguous_diagrams_prefer_player_1_as_first_player()
+	{
+		let field = Field::from_diagram("....\n....\n....\n.XO.").unwrap();
+		assert_eq!(field.get_first_player(), PlayerId::P1);
+		let field = Field::from_diagram("....\n....\nX...\nO...").unwrap(); //only possible if O started
+		assert_eq!(field.get_first_player(), PlayerId::P2);
+		assert_eq!(field.get_player_to_move(), PlayerId::P2);
+	}
+	
+	#[test]
+	fn invalid_diagrams()
+	{
+		assert_eq!(Field::from_diagram("....\n.A..").err(), Some(PositionError::InvalidCell(1, 'A')));
+		assert_eq!(Field::from_diagram("....\n....\n....\n...").err(), Some(PositionError::WrongSize(4, 4)));
+		assert_eq!(Field::from_diagram("X").err(), Some(PositionError::WrongSize(1, 1)));
+		assert_eq!(Field::new(7, 6, 4).load_diagram("....\n....\n....").err(), Some(PositionError::WrongSize(4, 3)));
+		assert_eq!(Field::from_diagram("....\n.X..\n....\n.O..").err(), Some(PositionError::FloatingStone(1, 1)));
+		assert_eq!(Field::from_diagram("....\n....\n....\nXXX.").err(), Some(PositionError::StoneCount(3, 0)));
+		assert_eq!(Field::from_diagram("XO..\nXO..\nXO..\nXO..").err(), Some(PositionError::TwoWinners));
+		//X won, but O played last
+		assert_eq!(Field::from_diagram("X...\nXO..\nXO..\nXOOO").err(), Some(PositionError::Unreachable));
+		//the game ended with the first row
+		assert_eq!(Field::from_diagram("X.....X\nX.....X\nXOO.OOX\nXOOXOOX").err(), Some(PositionError::Unreachable));
+		
+		//the field is unchanged after errors
+		let mut field = Field::from_moves("44").unwrap();
+		assert!(field.load_diagram("X").is_err());
+		assert_eq!(field.to_moves(), "44");
+	}
+	
+	const SHAPES:[(u32, u32, u32); 8] = [(7, 6, 4), (4, 4, 4), (8, 5, 4), (5, 8, 4), (9, 3, 3), (3, 9, 3), (6, 6, 5), (2, 7, 2)];
+	
+	/// stones of a row of the given length from (x, y) in direction (dx, dy), None if it leaves the board
+	fn row(w:u32, h:u32, len:u32, x:i32, y:i32, dx:i32, dy:i32) -> Option<u64>
+	{
+		let mut stones = 0u64;
+		for i in 0..len as i32
+		{
+			let (cx, cy) = (x + i*dx, y + i*dy);
+			if cx < 0 || cy < 0 || cx >= w as i32 || cy >= h as i32 { return None; }
+			stones |= 1u64 << (cy as u32*w + cx as u32);
+		}
+		Some(stones)
+	}
+	
+	/// checks for a win by trying every row on the board
+	fn brute_force_won(w:u32, h:u32, win:u32, stones:u64) -> bool
+	{
+		for &(dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)].iter()
+		{
+			for y in 0..h as i32
+			{
+				for x in 0..w as i32
 				{
-					match self.get_val(x, y)
+					if let Some(row) = row(w, h, win, x, y, dx, dy)
 					{
-						1 => { return 1; },
-						2 => { return 2; },
-						_ => { continue; },
+						if stones & row == row { return true; }
 					}
 				}
 			}
 		}
+		false
+	}
+	
+	#[test]
+	fn winning_rows_in_all_directions_and_at_the_edges()
+	{
+		for &(w, h, win) in SHAPES.iter()
+		{
+			let field = Field::new(w, h, win);
+			for &(dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)].iter()
+			{
+				for y in 0..h as i32
+				{
+					for x in 0..w as i32
+					{
+						if let Some(stones) = row(w, h, win, x, y, dx, dy)
+						{ //every row on the board wins, one stone less does not
+							assert!(field.has_won(stones), "{}x{}, win {}: row from ({}, {}) in direction ({}, {})", w, h, win, x, y, dx, dy);
+							let short = row(w, h, win - 1, x, y, dx, dy).unwrap();
+							assert!(!field.has_won(short), "{}x{}, win {}: short row from ({}, {})", w, h, win, x, y);
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	#[test]
+	fn rows_do_not_wrap_around_the_edges()
+	{
+		let field = Field::new(7, 6, 4);
+		assert!(!field.has_won((0b11 << 5) | (0b11 << 7))); //horizontal over the right edge into the next line
+		assert!(!field.has_won((1 << 5) | (1 << 13) | (1 << 21) | (1 << 29))); //diagonal over the right edge
+		assert!(!field.has_won((1 << 8) | (1 << 14) | (1 << 20) | (1 << 26))); //anti-diagonal over the left edge
+		assert!(!field.has_won((1 << 27) | (1 << 34) | (1 << 41) | (1 << 6))); //vertical over the bottom
 		
-		if self.is_full() { return -1; }
-		0
+		let mut seed = 0x2545_f491_4f6c_dd1du64;
+		for &(w, h, win) in SHAPES.iter()
+		{
+			let field = Field::new(w, h, win);
+			for _ in 0..2000
+			{ //sparse random stones, so that both results occur
+				seed ^= seed << 13;
+				seed ^= seed >> 7;
+				seed ^= seed << 17;
+				let stones = (seed & seed.rotate_left(23)) & ((1u64 << (w*h)) - 1);
+				assert_eq!(field.has_won(stones), brute_force_won(w, h, win, stones), "{}x{}, win {}: stones {:b}", w, h, win, stones);
+			}
+		}
+	}
+	
+	#[test]
+	fn incremental_state_and_undo()
+	{
+		let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+		for &(w, h, win) in SHAPES.iter()
+		{
+			for _ in 0..50
+			{
+				let mut field = Field::new(w, h, win);
+				let mut history = Vec::new();
+				let mut player = PlayerId::P1;
+				while !field.get_state().is_over()
+				{
+					seed ^= seed << 13;
+					seed ^= seed >> 7;
+					seed ^= seed << 17;
+					let x = (seed % w as u64) as u32;
+					if !field.is_valid_play(x) { continue; }
+					history.push((field.get_hash(), field.get_mirror_hash(), field.get_state(), field.get_field(), field.get_player_to_move()));
+					field.play(player, x).unwrap();
+					assert_eq!(field.get_state(), field.scan_state(), "{}x{}, win {}: {}", w, h, win, field.to_moves());
+					assert_ne!(field.get_hash(), history.last().unwrap().0);
+					player = player.other();
+				}
+				
+				//a new field with the same moves is the same position
+				let mut replay = Field::new(w, h, win);
+				for (i, &(x, _)) in field.get_turns().iter().enumerate()
+				{
+					replay.play(if i % 2 == 0 { PlayerId::P1 } else { PlayerId::P2 }, x).unwrap();
+				}
+				assert_eq!(replay.get_hash(), field.get_hash());
+				assert_eq!(replay.get_state(), field.get_state());
+				
+				//undo restores every previous position
+				while let Some((hash, mirror_hash, state, cells, to_move)) = history.pop()
+				{
+					assert!(field.undo());
+					assert_eq!(field.get_hash(), hash);
+					assert_eq!(field.get_mirror_hash(), mirror_hash);
+					assert_eq!(field.get_state(), state);
+					assert_eq!(field.get_field(), cells);
+					assert_eq!(field.get_player_to_move(), to_move);
+				}
+				assert!(!field.undo());
+				assert_eq!(field.get_hash(), Field::new(w, h, win).get_hash());
+			}
+		}
 	}
 }
+
@@ -1,89 +1,320 @@
 #![allow(dead_code)]
 
-mod field;
-mod player;
+pub mod field;
+pub mod player;
+pub mod error;
+pub mod solver;
+pub mod config;
+pub mod context;
 
-use self::field::Field;
-use self::player::Player;
-use self::player::io_player::PlayerIO;
-use self::player::random_player::PlayerRandom;
-use self::player::ai_q_player::PlayerAIQ;
-use self::player::minimax_player::PlayerMinimax;
-use self::player::ai_qoff_player::PlayerAIQOff;
-use self::player::ai_value_player::PlayerAIValue;
+pub use self::error::Error;
+pub use self::field::{Field, PositionError, DEFAULT_WIDTH, DEFAULT_HEIGHT, DEFAULT_WIN, PlayerId, Cell, GameState};
+pub use self::player::{Player, Analyze, SearchInfo};
+pub use self::solver::Solver;
+pub use self::config::Config;
+pub use self::context::GameContext;
+use self::player::{PlayerIO, PlayerRandom, PlayerAIQ, PlayerMinimax, PlayerAIQOff, PlayerAIValue, PlayerSolver, PlayerMCTS, PlayerAlphaZero};
+use self::player::mcts_player::Rollout;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 
-#[derive(Debug)]
-pub enum PlayerType {None, IO, Random, AIQ, AIQFixed, AIQPlay, Minimax, AIQOff, AIQOffFixed, AIValue, AIValueFixed}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerType {None, IO, Random, AIQ, AIQFixed, AIQPlay, Minimax, AIQOff, AIQOffFixed, AIValue, AIValueFixed, Solver, MCTS, MCTSRandom, AlphaZero, AlphaZeroFixed}
+
+/// what happens when a player answers with a full or nonexistent column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IllegalMovePolicy
+{
+	Forfeit, //the player loses the game
+	Retry(u32), //the player is asked again up to the given number of times, then it forfeits
+}
+
+/// player type with its settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSpec
+{
+	pub ptype: PlayerType,
+	pub config: Config, //only the section of the player type is used
+	name: String, //type and changed settings, like Minimax(depth=8)
+}
+
+impl PlayerSpec
+{
+	pub fn new(ptype:PlayerType) -> PlayerSpec
+	{
+		PlayerSpec::with_config(ptype, Config::default())
+	}
+	
+	pub fn with_config(ptype:PlayerType, config:Config) -> PlayerSpec
+	{
+		PlayerSpec { ptype, config, name: format!("{:?}", ptype) }
+	}
+	
+	/// changes a setting of the player, like depth to 8 for minimax
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		self.config.set_for(self.ptype, key, value)?;
+		let setting = format!("{}={}", key.trim().to_lowercase(), value.trim());
+		if self.name.ends_with(')')
+		{
+			self.name.pop();
+			self.name = format!("{}, {})", self.name, setting);
+		}
+		else { self.name = format!("{}({})", self.name, setting); }
+		Ok(())
+	}
+}
+
+impl From<PlayerType> for PlayerSpec
+{
+	fn from(ptype:PlayerType) -> PlayerSpec
+	{
+		PlayerSpec::new(ptype)
+	}
+}
+
+impl fmt::Display for PlayerSpec
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", self.name)
+	}
+}
 
 pub struct Game
 {
 	field: Field,
-	p1: Option<Box<Player>>,
-	p2: Option<Box<Player>>,
-	startp: u32
+	p1: Option<Box<dyn Player>>,
+	p2: Option<Box<dyn Player>>,
+	startp: PlayerId,
+	opening: String, //moves played at the start of every game
+	move_time: Option<Duration>, //time limit per move of the searching players
+	print_info: bool, //print the search info after every move
+	illegal_moves: IllegalMovePolicy,
+	context: GameContext, //history, clocks and match of the current game
+	autosave: u32, //save the learning players every that many games, 0 never
+	games: u32, //games played with the current players
+	interrupt: Option<Arc<AtomicBool>>, //stops playing when set
+	model_dir: String, //directory of the model files of the learning players, empty for the current one
 }
 
 impl Game
 {
 	pub fn new() -> Game
 	{
-		Game { field: Field::new(7, 6), p1: None, p2: None, startp: 1 }
+		Game::with_rules(DEFAULT_WIDTH, DEFAULT_HEIGHT, DEFAULT_WIN).unwrap() //standard rules are supported
 	}
 	
-	fn map_player(p:PlayerType) -> Option<Box<Player>>
+	/// game on a board of different size (error if the players can not handle the size)
+	pub fn with_size(width:u32, height:u32) -> Result<Game, Error>
 	{
+		Game::with_rules(width, height, DEFAULT_WIN)
+	}
+	
+	/// game on the standard board with a different number of stones in a row to win (error if it is not possible to win)
+	pub fn with_win_length(win:u32) -> Result<Game, Error>
+	{
+		Game::with_rules(DEFAULT_WIDTH, DEFAULT_HEIGHT, win)
+	}
+	
+	/// game with custom board size and win length (error if these rules are not supported)
+	pub fn with_rules(width:u32, height:u32, win:u32) -> Result<Game, Error>
+	{
+		if !Field::is_supported(width, height, win) { return Err(Error::UnsupportedRules(width, height, win)); }
+		let field = Field::new(width, height, win);
+		Ok(Game { context: GameContext::new(&field), field, p1: None, p2: None, startp: PlayerId::P1, opening: String::new(), move_time: None, print_info: false,
+				illegal_moves: IllegalMovePolicy::Forfeit, autosave: 0, games: 0, interrupt: None,
+				model_dir: String::new() })
+	}
+	
+	/// creates a player of the given type with its settings and the ones of this game (not initialized)
+	pub fn create_player(&self, spec:&PlayerSpec) -> Option<Box<dyn Player>>
+	{
+		let (p, config) = (spec.ptype, &spec.config);
 		match p
 		{
 			PlayerType::None => None,
 			PlayerType::IO => Some(PlayerIO::new()),
 			PlayerType::Random => Some(PlayerRandom::new()),
-			PlayerType::AIQ => Some(PlayerAIQ::new(false, true)),
-			PlayerType::AIQFixed => Some(PlayerAIQ::new(true, true)),
-			PlayerType::AIQPlay => Some(PlayerAIQ::new(true, false)),
-			PlayerType::Minimax => Some(PlayerMinimax::new()),
-			PlayerType::AIQOff => Some(PlayerAIQOff::new(false)),
-			PlayerType::AIQOffFixed => Some(PlayerAIQOff::new(true)),
-			PlayerType::AIValue => Some(PlayerAIValue::new(false)),
-			PlayerType::AIValueFixed => Some(PlayerAIValue::new(true)),
+			PlayerType::AIQ | PlayerType::AIQFixed | PlayerType::AIQPlay =>
+				{
+					let mut player = PlayerAIQ::with_config(p != PlayerType::AIQ, p != PlayerType::AIQPlay, config.aiq.clone());
+					player.set_model_dir(&self.model_dir);
+					Some(player)
+				},
+			PlayerType::Minimax =>
+				{
+					let mut player = PlayerMinimax::with_config(config.minimax.clone());
+					player.set_time_limit(self.move_time);
+					Some(player)
+				},
+			PlayerType::AIQOff | PlayerType::AIQOffFixed =>
+				{
+					let mut player = PlayerAIQOff::with_config(p == PlayerType::AIQOffFixed, config.aiqoff.clone());
+					player.set_model_dir(&self.model_dir);
+					Some(player)
+				},
+			PlayerType::AIValue | PlayerType::AIValueFixed =>
+				{
+					let mut player = PlayerAIValue::with_config(p == PlayerType::AIValueFixed, config.aivalue.clone());
+					player.set_time_limit(self.move_time);
+					player.set_model_dir(&self.model_dir);
+					Some(player)
+				},
+			PlayerType::Solver => Some(PlayerSolver::with_config(config.solver.clone())),
+			PlayerType::MCTS | PlayerType::MCTSRandom =>
+				{
+					let mut player = PlayerMCTS::with_config(if p == PlayerType::MCTS { Rollout::Heuristic } else { Rollout::Random }, config.mcts.clone());
+					player.set_time_limit(self.move_time);
+					Some(player)
+				},
+			PlayerType::AlphaZero | PlayerType::AlphaZeroFixed =>
+				{
+					let mut player = PlayerAlphaZero::with_config(p == PlayerType::AlphaZeroFixed, config.alphazero.clone());
+					player.set_time_limit(self.move_time);
+					player.set_model_dir(&self.model_dir);
+					Some(player)
+				},
 		}
 	}
 	
-	pub fn set_player1(&mut self, p:PlayerType) -> bool
+	pub fn set_player1<P:Into<PlayerSpec>>(&mut self, p:P) -> Result<(), Error>
 	{
-		self.p1 = Game::map_player(p);
+		let spec = p.into();
+		let name = spec.to_string();
+		self.p1 = self.create_player(&spec);
+		self.games = 0;
+		self.context.players[0] = if self.p1.is_some() { name.clone() } else { String::new() };
 		
 		if self.p1.is_some()
 		{
-			if !self.p1.as_mut().unwrap().init(&self.field, 1)
+			if let Err(err) = self.p1.as_mut().unwrap().init(&self.field, PlayerId::P1, &self.context)
 			{
 				self.p1 = None;
-				return false;
+				return Err(Error::PlayerInit(name, Box::new(err)));
 			}
 		}
-		true
+		Ok(())
 	}
 	
-	pub fn set_player2(&mut self, p:PlayerType) -> bool
+	pub fn set_player2<P:Into<PlayerSpec>>(&mut self, p:P) -> Result<(), Error>
 	{
-		self.p2 = Game::map_player(p);
+		let spec = p.into();
+		let name = spec.to_string();
+		self.p2 = self.create_player(&spec);
+		self.games = 0;
+		self.context.players[1] = if self.p2.is_some() { name.clone() } else { String::new() };
 		
 		if self.p2.is_some()
 		{
-			if !self.p2.as_mut().unwrap().init(&self.field, 2)
+			if let Err(err) = self.p2.as_mut().unwrap().init(&self.field, PlayerId::P2, &self.context)
 			{
 				self.p2 = None;
-				return false;
+				return Err(Error::PlayerInit(name, Box::new(err)));
 			}
 		}
-		true
+		Ok(())
+	}
+	
+	/// time limit per move for the searching players (minimax, AIValue, MCTS, AlphaZero), None lets them search to their fixed depth (or number of iterations).
+	/// only affects players set afterwards
+	pub fn set_move_time(&mut self, time:Option<Duration>)
+	{
+		self.move_time = time;
 	}
 	
-	pub fn set_start_player(&mut self, p:u32) -> bool
+	/// thinking time of every player per game, exceeding it loses the game. None plays without clock
+	pub fn set_game_time(&mut self, time:Option<Duration>)
+	{
+		self.context.game_time = time;
+	}
+	
+	/// marks the games as training of the learning players (default: false, evaluation)
+	pub fn set_training(&mut self, training:bool)
+	{
+		self.context.training = training;
+	}
+	
+	/// history, clocks and match of the current (or last) game
+	pub fn get_context(&self) -> &GameContext
+	{
+		&self.context
+	}
+	
+	/// directory of the model files of the learning players, that are set later and do not choose a file in their settings.
+	/// the current directory by default
+	pub fn set_model_dir(&mut self, dir:&str)
+	{
+		self.model_dir = dir.to_string();
+	}
+	
+	/// saves the models of the learning players after every "every" games, 0 only saves when they are dropped (default)
+	pub fn set_autosave(&mut self, every:u32)
+	{
+		self.autosave = every;
+	}
+	
+	/// flag to stop playing, e.g. set by a Ctrl-C handler: the current game is aborted after the current move and
+	/// play returns Error::Interrupted (the players can be saved afterwards)
+	pub fn set_interrupt(&mut self, flag:Arc<AtomicBool>)
+	{
+		self.interrupt = Some(flag);
+	}
+	
+	/// writes the model of player p, if it learns
+	pub fn save_player(&mut self, p:PlayerId) -> Result<(), Error>
+	{
+		let player = if p == PlayerId::P1 { self.p1.as_mut() } else { self.p2.as_mut() };
+		match player
+		{
+			Some(player) => player.save(),
+			None => Ok(()),
+		}
+	}
+	
+	/// writes the models of both players, if they learn
+	pub fn save_players(&mut self) -> Result<(), Error>
+	{
+		self.save_player(PlayerId::P1)?;
+		self.save_player(PlayerId::P2)
+	}
+	
+	/// how illegal moves of the players are handled, they forfeit by default
+	pub fn set_illegal_move_policy(&mut self, policy:IllegalMovePolicy)
+	{
+		self.illegal_moves = policy;
+	}
+	
+	/// prints what the searching players found after each of their moves
+	pub fn set_print_info(&mut self, print:bool)
+	{
+		self.print_info = print;
+	}
+	
+	/// search info of the last move of player p, None if it does not search or did not move yet
+	pub fn search_info(&self, p:PlayerId) -> Option<&SearchInfo>
+	{
+		let player = if p == PlayerId::P1 { self.p1.as_ref() } else { self.p2.as_ref() };
+		player.and_then(|player| player.search_info())
+	}
+	
+	pub fn set_start_player(&mut self, p:PlayerId)
 	{
-		if p < 1 || p > 2 { return false; }
 		self.startp = p;
-		true
+	}
+	
+	/// sets the moves (1-based columns, like "4453") to start every game with, the start player does the first move
+	pub fn set_opening(&mut self, moves:&str) -> Result<(), PositionError>
+	{
+		let mut field = self.field.clone();
+		field.reset();
+		field.play_moves(moves)?;
+		if field.get_state().is_over() { return Err(PositionError::GameOver(field.get_turns().len())); }
+		self.opening = field.to_moves();
+		Ok(())
 	}
 	
 	pub fn is_ready(&self) -> bool
@@ -91,60 +322,98 @@ impl Game
 		self.p1.is_some() && self.p2.is_some()
 	}
 	
-	pub fn play(&mut self) -> bool
+	pub fn play(&mut self) -> Result<GameState, Error>
 	{
-		if !self.is_ready() { return false; }
+		if !self.is_ready() { return Err(Error::NoPlayer); }
 		
 		let p1 = self.p1.as_mut().unwrap();
 		let p2 = self.p2.as_mut().unwrap();
 		
 		self.field.reset();
-		let mut turn1:bool = self.startp == 1;
-		let mut state = 0;
-		p1.startp(self.startp as i32);
-		p2.startp(self.startp as i32);
+		self.field.play_moves_from(self.startp, &self.opening)?;
+		self.context.start_player = self.startp;
+		self.context.history = self.field.get_turns().iter().map(|&(x, _)| x).collect();
+		self.context.time_used = [Duration::new(0, 0); 2];
+		let mut state = GameState::Running;
+		p1.startp(self.startp);
+		p2.startp(self.startp);
 		
-		while state == 0
+		while !state.is_over()
 		{
-			if turn1
-			{
-				if !p1.play(&mut self.field)
-				{ println!("Warning: player 1 did not play!"); }
+			if self.interrupt.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) { return Err(Error::Interrupted); }
+			let pid = if self.field.get_turns().is_empty() { self.startp } else { self.field.get_player_to_move() }; //the empty field does not know the start player
+			let player = if pid == PlayerId::P1 { &mut *p1 } else { &mut *p2 };
+			let mut retries = 0;
+			loop
+			{ //the player only chooses the column, the game checks and plays it
+				let start = Instant::now();
+				let x = player.play(&self.field, &self.context)?;
+				self.context.time_used[pid.index()] += start.elapsed();
+				if self.context.remaining(pid) == Some(Duration::new(0, 0))
+				{
+					println!("Warning: player {} lost on time!", pid);
+					state = GameState::Win(pid.other());
+					break;
+				}
+				match self.field.play(pid, x)
+				{
+					Ok(()) =>
+						{
+							self.context.history.push(x);
+							state = self.field.get_state();
+							break;
+						},
+					Err(err) =>
+						{
+							let retry = match self.illegal_moves { IllegalMovePolicy::Retry(max) => retries < max, IllegalMovePolicy::Forfeit => false };
+							if retry { retries += 1; continue; }
+							println!("Warning: player {} forfeits the game: {}", pid, err);
+							state = GameState::Win(pid.other());
+							break;
+						},
+				}
 			}
-			else
+			if self.print_info
 			{
-				if !p2.play(&mut self.field)
-				{ println!("Warning: player 2 did not play!"); }
+				if let Some(info) = player.search_info() { println!("{}: {}", pid, info); }
 			}
-			turn1 = !turn1;
-			state = self.field.get_state();
 			//self.field.print(); //debug
 		}
 		
-		p1.outcome(&mut self.field, state);
-		p2.outcome(&mut self.field, state);
+		p1.outcome(&self.field, state, &self.context);
+		p2.outcome(&self.field, state, &self.context);
 		
-		true
+		self.games += 1;
+		if self.autosave > 0 && self.games % self.autosave == 0
+		{
+			p1.save()?;
+			p2.save()?;
+		}
+		
+		Ok(state)
 	}
 	
-	pub fn play_many(&mut self, num:u32, every:u32) -> (f64, f64)
+	/// plays num games, switching sides every "every" games (at least 1). returns the win rates of player 1 and 2 in percent
+	pub fn play_many(&mut self, num:u32, every:u32) -> Result<(f64, f64), Error>
 	{
-		if num<1 { return (-1.0, -1.0); }
+		if every < 1 { return Err(Error::InvalidArgument("games per side have to be at least 1".to_string())); }
+		if num<1 { return Ok((0.0, 0.0)); }
 		
 		let mut p1win:u32 = 0;
 		let mut draw:u32 = 0;
 		let mut p2win:u32 = 0;
 		
+		self.context.games = num;
 		for i in 0..num
 		{
-			if i > 0 && i%every == 0 { self.startp = if self.startp == 1 { 2 } else { 1 }; } //switch sides every "every" game
-			if !self.play() { return (-1.0, -1.0); }
-			match self.field.get_state()
+			if i > 0 && i%every == 0 { self.startp = self.startp.other(); } //switch sides every "every" game
+			self.context.game = i;
+			match self.play()?
 			{
-				-1 => draw += 1,
-				1 => p1win += 1,
-				2 => p2win += 1,
-				_ => println!("Warning: game ended running!"),
+				GameState::Draw => draw += 1,
+				GameState::Win(PlayerId::P1) => p1win += 1,
+				GameState::Win(PlayerId::P2) => p2win += 1,
+				GameState::Running => println!("Warning: game ended running!"),
 			}
 		}
 		
@@ -159,6 +428,54 @@ impl Game
 		println!("Player O wins: {:>6.2}% ({}/{})", p2wr, p2win, num);
 		println!("");
 		
-		(p1wr, p2wr)
+		Ok((p1wr, p2wr))
 	}
 }
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{Game, PlayerType, PlayerSpec, PlayerId, Cell, Error};
+	
+	#[test]
+	fn start_player_does_the_first_move()
+	{
+		for &startp in [PlayerId::P1, PlayerId::P2].iter()
+		{
+			let mut game = Game::new();
+			game.set_player1(PlayerType::Random).unwrap();
+			game.set_player2(PlayerType::Random).unwrap();
+			game.set_start_player(startp);
+			game.play().unwrap();
+			let (x, y) = game.field.get_turns()[0];
+			assert_eq!(game.field.get_val(x, y), Cell::Stone(startp));
+			assert_eq!(game.field.get_first_player(), startp);
+		}
+	}
+	
+	#[test]
+	fn zero_frequencies_are_rejected()
+	{
+		for &(ptype, key) in [(PlayerType::AIValue, "learn_freq"), (PlayerType::AlphaZero, "learn_freq"), (PlayerType::AIQ, "target_update")].iter()
+		{
+			let mut spec = PlayerSpec::new(ptype);
+			spec.set(key, "0").unwrap();
+			match Game::new().set_player1(spec)
+			{
+				Err(Error::PlayerInit(_, cause)) => match *cause { Error::InvalidConfig(_) => (), ref err => panic!("{:?}: {}", ptype, err) },
+				_ => panic!("{:?} accepts {} 0", ptype, key),
+			}
+		}
+		
+		let mut game = Game::new();
+		game.set_player1(PlayerType::Random).unwrap();
+		game.set_player2(PlayerType::Random).unwrap();
+		match game.play_many(2, 0)
+		{
+			Err(Error::InvalidArgument(_)) => (),
+			_ => panic!("0 games per side are accepted"),
+		}
+	}
+}
+
@@ -5,15 +5,17 @@ extern crate rand;
 extern crate nn;
 extern crate rustc_serialize;
 
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::io::prelude::*;
 use self::rustc_serialize::json;
 use self::rand::Rng;
 use self::nn::{NN, HaltCondition, Activation};
-use super::Player;
-use super::super::field::Field;
+use super::{Player, Analyze};
+use super::model;
+use super::super::config::{parse_value, parse_list, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState, Cell};
 
+//defaults of the settings
 const GAMMA:f64 = 0.99; //q gamma (action-reward time difference high) (not 1.0 as the game terminates)
 const LR:f64 = 0.05; //neural net learning rate (deterministic -> high)
 const LR_DECAY:f64 = 0.01 / 20000f64; //NN learning rate decrease per game(s)
@@ -30,15 +32,79 @@ const TARGET_UPDATE:u32 = 250; //number of games between target NN updates
 const OBSERVE:u32 = 1000; //don't learn the first games, just fill experience buffer
 
 
+/// settings of the AIQ player
+#[derive(Debug, Clone, PartialEq)]
+pub struct AIQConfig
+{
+	pub gamma: f64, //q gamma
+	pub lr: f64, //neural net learning rate
+	pub lr_decay: f64, //learning rate decrease per game
+	pub lr_min: f64, //minimum learning rate
+	pub lambda: f64, //L2 regularization parameter
+	pub momentum: f64, //neural net momentum
+	pub rnd_pick_start: f64, //exploration factor start
+	pub rnd_pick_dec: f64, //random exploration decrease (half every rnd_pick_dec games)
+	pub rnd_pick_min: f64, //exploration rate minimum
+	pub exp_rep_size: usize, //size of the experience replay buffer
+	pub exp_rep_batch: u32, //batch size for replay training
+	pub epochs: u32, //training epochs for a mini batch
+	pub target_update: u32, //number of games between target NN updates
+	pub observe: u32, //games to only fill the experience buffer before learning
+	pub layers: Vec<u32>, //hidden layer sizes of a new neural net, empty: 6n, 3n, n for n squares
+	pub model: String, //file of the model, empty: AIQ-<rules>.NN (like AIQ-7x6.NN) in the models directory
+}
+
+impl Default for AIQConfig
+{
+	fn default() -> AIQConfig
+	{
+		AIQConfig { gamma: GAMMA, lr: LR, lr_decay: LR_DECAY, lr_min: LR_MIN, lambda: LAMBDA, momentum: MOM, rnd_pick_start: RND_PICK_START,
+				rnd_pick_dec: RND_PICK_DEC, rnd_pick_min: RND_PICK_MIN, exp_rep_size: EXP_REP_SIZE, exp_rep_batch: EXP_REP_BATCH, epochs: EPOCHS,
+				target_update: TARGET_UPDATE, observe: OBSERVE, layers: Vec::new(), model: String::new() }
+	}
+}
+
+impl AIQConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"gamma" => self.gamma = parse_value(key, value)?,
+			"lr" => self.lr = parse_value(key, value)?,
+			"lr_decay" => self.lr_decay = parse_value(key, value)?,
+			"lr_min" => self.lr_min = parse_value(key, value)?,
+			"lambda" => self.lambda = parse_value(key, value)?,
+			"momentum" => self.momentum = parse_value(key, value)?,
+			"rnd_pick_start" => self.rnd_pick_start = parse_value(key, value)?,
+			"rnd_pick_dec" => self.rnd_pick_dec = parse_value(key, value)?,
+			"rnd_pick_min" => self.rnd_pick_min = parse_value(key, value)?,
+			"exp_rep_size" => self.exp_rep_size = parse_value(key, value)?,
+			"exp_rep_batch" => self.exp_rep_batch = parse_value(key, value)?,
+			"epochs" => self.epochs = parse_value(key, value)?,
+			"target_update" => self.target_update = parse_value(key, value)?,
+			"observe" => self.observe = parse_value(key, value)?,
+			"layers" => self.layers = parse_list(key, value)?,
+			"model" => self.model = value.trim().to_string(),
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
 pub struct PlayerAIQ
 {
 	initialized: bool,
 	fixed: bool, //should the agent learn or not (fixed => dont learn)
 	filename: String,
-	pid: i32, //player ID
+	model_dir: String, //directory of the model file, if no file is chosen in the settings (empty: current directory)
+	pid: PlayerId, //player ID
+	config: AIQConfig,
 	nn: Option<NN>, //online network
 	targetnn: Option<NN>, //target network (temporarely fixed value network)
 	games_played: u32,
+	games_saved: u32, //games played when the model file was written last
 	lr: f64,
 	exploration: f64,
 	explore: bool, //should the agent explore randomly sometimes?
@@ -62,20 +128,31 @@ impl PlayerAIQ
 {
 	pub fn new(fix:bool, exp:bool) -> Box<PlayerAIQ>
 	{
-		Box::new(PlayerAIQ { initialized: false, fixed: fix, filename: String::new(), pid: 0,
-				nn: None, targetnn: None, games_played: 0, lr: LR, exploration: RND_PICK_START,
-				explore: exp, startp: 0.0, exp_buffer: None,
+		PlayerAIQ::with_config(fix, exp, AIQConfig::default())
+	}
+	
+	pub fn with_config(fix:bool, exp:bool, config:AIQConfig) -> Box<PlayerAIQ>
+	{
+		Box::new(PlayerAIQ { initialized: false, fixed: fix, filename: String::new(), model_dir: String::new(), pid: PlayerId::P1,
+				nn: None, targetnn: None, games_played: 0, games_saved: 0, lr: config.lr, exploration: config.rnd_pick_start,
+				explore: exp, startp: 0.0, exp_buffer: None, config,
 				memstate: Vec::new(), memqval: Vec::new(), memreward: REW_FLAG, memplay: 0 })
 	}
 	
+	/// directory of the model file, unless the model setting chooses a file
+	pub fn set_model_dir(&mut self, dir:&str)
+	{
+		self.model_dir = dir.to_string();
+	}
+	
 	fn get_exploration(&self) -> f64
 	{
-		RND_PICK_MIN.max(RND_PICK_START * (2f64).powf(-(self.games_played as f64)/RND_PICK_DEC))
+		self.config.rnd_pick_min.max(self.config.rnd_pick_start * (2f64).powf(-(self.games_played as f64)/self.config.rnd_pick_dec))
 	}
 	
 	fn get_lr(&self) -> f64
 	{
-		LR_MIN.max(LR - LR_DECAY * self.games_played as f64)
+		self.config.lr_min.max(self.config.lr - self.config.lr_decay * self.games_played as f64)
 	}
 	
 	fn argmax(slice:&[f64]) -> u32
@@ -94,19 +171,20 @@ impl PlayerAIQ
 	}
 	
 	//field and extra info
-	/*fn field_to_input(field:&mut Field, p:i32, startp:f64) -> Vec<f64>
+	/*fn field_to_input(field:&Field, p:i32, startp:f64) -> Vec<f64>
 	{
 		let op:i32 = if p == 1 { 2 } else { 1 }; //other player
 		let mut input:Vec<f64> = Vec::with_capacity((2*field.get_size() + field.get_w() + 1) as usize);
 		//2 nodes for every square: -1 enemy, 0 free, 1 own; 0 square will not be reached with one move, 1 square can be directly filled
-		for (i, val) in field.get_field().iter().enumerate()
+		let cells = field.get_field();
+		for (i, val) in cells.iter().enumerate()
 		{
 			if *val == p { input.push(1f64); input.push(0f64); }
 			else if *val == op { input.push(-1f64); input.push(0f64); }
 			else
 			{ //empty square
 				input.push(0f64);
-				if (i as u32) < (field.get_size()-field.get_w()) { input.push(if field.get_field()[i+field.get_w() as usize] != 0 { 1f64 } else { 0f64 }); }
+				if (i as u32) < (field.get_size()-field.get_w()) { input.push(if cells[i+field.get_w() as usize] != 0 { 1f64 } else { 0f64 }); }
 				else { input.push(1f64); }
 			}
 		}
@@ -140,16 +218,18 @@ impl PlayerAIQ
 	}*/
 	
 	//raw field
-	fn field_to_input(field:&mut Field, p:i32, startp:f64) -> Vec<f64>
+	fn field_to_input(field:&Field, p:PlayerId, startp:f64) -> Vec<f64>
 	{
-		let op:i32 = if p == 1 { 2 } else { 1 }; //other player
 		let mut input:Vec<f64> = Vec::with_capacity((field.get_size() + 1) as usize);
 		//1 nodes for every square: -1 enemy, 0 free, 1 own
 		for val in field.get_field().iter()
 		{
-			if *val == p { input.push(1f64); }
-			else if *val == op { input.push(-1f64); }
-			else { input.push(0f64); } //empty square
+			match *val
+			{
+				Cell::Stone(q) if q == p => input.push(1f64),
+				Cell::Stone(_) => input.push(-1f64),
+				Cell::Empty => input.push(0f64), //empty square
+			}
 		}
 		//1 node for starting player (-1 enemy, 1 self)
 		input.push(startp);
@@ -160,51 +240,47 @@ impl PlayerAIQ
 
 impl Player for PlayerAIQ
 {
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	#[allow(unused_variables)]
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
+		if self.config.target_update < 1 { return Err(Error::InvalidConfig("target update frequency has to be at least 1".to_string())); }
+		
 		self.pid = p;
 		
-		self.filename = format!("AIQ-{}x{}.NN", field.get_w(), field.get_h());
-		let file = File::open(&self.filename);
-		if file.is_err()
-		{
-			//create new neural net, as it could not be loaded
-			let n = field.get_size();
-			let w = field.get_w();
-			//self.nn = Some(NN::new(&[2*n+w+1, 3*n, n, w], Activation::PELU, Activation::Sigmoid)); //set size of NN layers here, be careful with activation function
-			self.nn = Some(NN::new(&[n+1, 6*n, 3*n, n, w], Activation::PELU, Activation::Sigmoid)); //set size of NN layers here, be careful with activation function
-			self.exp_buffer = Some(Vec::with_capacity(EXP_REP_SIZE));
-			//games_played, exploration, lr already set
-		}
-		else
+		self.filename = model::path(&self.config.model, &self.model_dir, &format!("AIQ-{}.NN", field.get_rules_tag()));
+		match model::load(&self.filename)?
 		{
-			//load neural net from file (and games played)
-			let mut reader = BufReader::new(file.unwrap());
-			let mut datas = String::new();
-			let mut nns = String::new();
-			let mut exps = String::new();
-			
-			let res1 = reader.read_line(&mut datas);
-			let res2 = reader.read_line(&mut nns);
-			let res3 = reader.read_to_string(&mut exps);
-			if res1.is_err() || res2.is_err() || res3.is_err() { return false; }
-			
-			let res = datas.trim().parse::<u32>();
-			if res.is_err() { return false; }
-			self.games_played = res.unwrap();
-			self.nn = Some(NN::from_json(&nns));
-			self.exp_buffer = Some(json::decode(&exps).unwrap());
-			
-			self.lr = self.get_lr();
-			self.exploration = self.get_exploration();
+			None =>
+				{
+					//create new neural net, as there is no file yet
+					let n = field.get_size();
+					let w = field.get_w();
+					//self.nn = Some(NN::new(&[2*n+w+1, 3*n, n, w], Activation::PELU, Activation::Sigmoid)); //set size of NN layers here, be careful with activation function
+					let mut layers = vec![n+1];
+					if self.config.layers.is_empty() { layers.extend_from_slice(&[6*n, 3*n, n]); } else { layers.extend_from_slice(&self.config.layers); }
+					layers.push(w);
+					self.nn = Some(NN::new(&layers, Activation::PELU, Activation::Sigmoid)); //be careful with activation function
+					self.exp_buffer = Some(Vec::with_capacity(self.config.exp_rep_size));
+					//games_played, exploration, lr already set
+				},
+			Some(model) =>
+				{
+					//neural net from file (and games played)
+					self.exp_buffer = Some(model::decode_data(&self.filename, &model.data)?);
+					self.games_played = model.games_played;
+					self.nn = Some(model.nn);
+					self.lr = self.get_lr();
+					self.exploration = self.get_exploration();
+				},
 		}
 		
 		self.targetnn = self.nn.clone();
+		self.games_saved = self.games_played;
 		self.initialized = true;
-		true
+		Ok(())
 	}
 	
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
 		if p == self.pid
 		{
@@ -216,9 +292,10 @@ impl Player for PlayerAIQ
 		}
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	#[allow(unused_variables)]
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
+		if !self.initialized { return Err(Error::NotInitialized); }
 		//variables
 		let mut rng = rand::thread_rng();
 		let nn = self.nn.as_mut().unwrap();
@@ -231,21 +308,21 @@ impl Player for PlayerAIQ
 		//learn if not fixed and not first move (reward is already set, won/loose would be outcome)
 		if !self.fixed && self.memreward != REW_FLAG
 		{
-			if self.games_played >= OBSERVE
+			if self.games_played >= self.config.observe
 			{
 				//get Q values for next state
 				let action = PlayerAIQ::argmax(&nn.run(&state)) as usize; //choose action by online NN
 				let qval2 = targetnn.run(&state); //use double q learning target nn, to decouple action and value a bit
 				let max = qval2[action]; //value by target NN
 				//calculate q update
-				self.memqval[self.memplay as usize] = (self.memreward + GAMMA * max) / (REW_MAX + GAMMA); //Q learning (divide to stay in [0,1] for sigmoid)
+				self.memqval[self.memplay as usize] = (self.memreward + self.config.gamma * max) / (REW_MAX + self.config.gamma); //Q learning (divide to stay in [0,1] for sigmoid)
 				//train on experience replay and the latest experience (q update)
 				let mut trainingset = Vec::new();
 				//experience
 				if exp_buffer.len() > 0
 				{
-					for _ in 0..EXP_REP_BATCH
-					{ //EXP_REP_BATCH random experiences to replay
+					for _ in 0..self.config.exp_rep_batch
+					{ //exp_rep_batch random experiences to replay
 						let repindex = rng.gen::<usize>() % exp_buffer.len();
 						let mut qval = nn.run(&exp_buffer[repindex].0); //.0 = state 1
 						if exp_buffer[repindex].2 == REW_LOSE || exp_buffer[repindex].2 == REW_WIN //.2 = reward
@@ -257,7 +334,7 @@ impl Player for PlayerAIQ
 							let action = PlayerAIQ::argmax(&nn.run(&exp_buffer[repindex].3)) as usize; //.3 = state 2
 							let qval2 = targetnn.run(&exp_buffer[repindex].3); //.3 = state 2
 							let max = qval2[action]; //value by target NN, action by online NN
-							qval[exp_buffer[repindex].1] = (exp_buffer[repindex].2 + GAMMA * max) / (REW_MAX + GAMMA); //.1 = action, .2 = reward //q update
+							qval[exp_buffer[repindex].1] = (exp_buffer[repindex].2 + self.config.gamma * max) / (REW_MAX + self.config.gamma); //.1 = action, .2 = reward //q update
 						}
 						trainingset.push((exp_buffer[repindex].0.clone(), qval));
 					}
@@ -265,16 +342,16 @@ impl Player for PlayerAIQ
 				//latest
 				trainingset.push((self.memstate.clone(), self.memqval.clone()));
 				nn.train(&trainingset)
-					.halt_condition(HaltCondition::Epochs(EPOCHS))
+					.halt_condition(HaltCondition::Epochs(self.config.epochs))
 					.log_interval(None)
 					//.log_interval(Some(2)) //debug
-					.momentum(MOM)
+					.momentum(self.config.momentum)
 					.rate(self.lr)
-					.lambda(LAMBDA / (self.games_played as f64 + 1000.0))
+					.lambda(self.config.lambda / (self.games_played as f64 + 1000.0))
 					.go();
 			}
 			//save latest as experience
-			if exp_buffer.len() >= EXP_REP_SIZE
+			if exp_buffer.len() >= self.config.exp_rep_size
 			{
 				exp_buffer.remove(0); //remove first element
 			}
@@ -290,41 +367,43 @@ impl Player for PlayerAIQ
 			self.memplay = rng.gen::<u32>() % field.get_w();
 		}
 		
-		//perform action and set reward
+		//check action and set reward
 		self.memreward = REW_NORMAL;
-		let mut res = field.play(self.pid, self.memplay);
-		if !res { self.memqval[self.memplay as usize] = REW_WRONG; } //move did not meet the rules, but learn anyway, even if another random move is made
+		if !field.is_valid_play(self.memplay) { self.memqval[self.memplay as usize] = REW_WRONG; } //move did not meet the rules, but learn anyway, even if another random move is made
 		
 		//random play when it was not rule conform, also modify q-value for it
-		while !res //infinite if it is already draw! (cannot happen without bug)
+		if field.get_state().is_over() { return Err(Error::GameOver); } //no move possible
+		while !field.is_valid_play(self.memplay)
 		{
 			self.memplay = rng.gen::<u32>() % field.get_w();
-			res = field.play(self.pid, self.memplay);
 		}
 		
 		//save state for next turn
 		self.memstate = state;
 		
 		//return
-		res
+		Ok(self.memplay)
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		if !self.fixed
 		{ //learn if not fixed (scope needed for "let nn" and "let targetnn" shortcut)
 			let mut exp_buffer = self.exp_buffer.as_mut().unwrap(); //shortcut
-			if self.games_played >= OBSERVE
+			if self.games_played >= self.config.observe
 			{
 				let nn = self.nn.as_mut().unwrap();
 				let targetnn = self.targetnn.as_mut().unwrap();
 				let mut rng = rand::thread_rng();
-				let op:i32 = if self.pid == 1 { 2 } else { 1 }; //other player
 				
 				//set reward (if draw, reward already set properly)
-				if state == self.pid { self.memreward = REW_WIN; }
-				else if state == op { self.memreward = REW_LOSE; }
+				match state.winner()
+				{
+					Some(p) if p == self.pid => self.memreward = REW_WIN,
+					Some(_) => self.memreward = REW_LOSE,
+					None => {},
+				}
 				
 				//end-values of network should meet reward exactly
 				self.memqval[self.memplay as usize] = self.memreward;
@@ -334,8 +413,8 @@ impl Player for PlayerAIQ
 				//experience
 				if exp_buffer.len() > 0
 				{
-					for _ in 0..EXP_REP_BATCH
-					{ //EXP_REP_BATCH experiences to replay
+					for _ in 0..self.config.exp_rep_batch
+					{ //exp_rep_batch experiences to replay
 						let repindex = rng.gen::<usize>() % exp_buffer.len();
 						let mut qval = nn.run(&exp_buffer[repindex].0); //.0 = state 1
 						if exp_buffer[repindex].2 == REW_LOSE || exp_buffer[repindex].2 == REW_WIN //.2 = reward
@@ -347,7 +426,7 @@ impl Player for PlayerAIQ
 							let action = PlayerAIQ::argmax(&nn.run(&exp_buffer[repindex].3)) as usize; //.3 = state 2
 							let qval2 = targetnn.run(&exp_buffer[repindex].3); //.3 = state 2
 							let max = qval2[action]; //value by target NN, action by online NN
-							qval[exp_buffer[repindex].1] = (exp_buffer[repindex].2 + GAMMA * max) / (REW_MAX + GAMMA); //.1 = action, .2 = reward //q update
+							qval[exp_buffer[repindex].1] = (exp_buffer[repindex].2 + self.config.gamma * max) / (REW_MAX + self.config.gamma); //.1 = action, .2 = reward //q update
 						}
 						trainingset.push((exp_buffer[repindex].0.clone(), qval));
 					}
@@ -355,18 +434,18 @@ impl Player for PlayerAIQ
 				//latest
 				trainingset.push((self.memstate.clone(), self.memqval.clone()));
 				nn.train(&trainingset)
-					.halt_condition(HaltCondition::Epochs(EPOCHS))
+					.halt_condition(HaltCondition::Epochs(self.config.epochs))
 					.log_interval(None)
 					//.log_interval(Some(2)) //debug
-					.momentum(MOM)
+					.momentum(self.config.momentum)
 					.rate(self.lr)
-					.lambda(LAMBDA / (self.games_played as f64 + 1000.0))
+					.lambda(self.config.lambda / (self.games_played as f64 + 1000.0))
 					.go();
 			}
 			//save latest as experience if not draw (would cause difficulties and is not as important)
 			if self.memreward != REW_NORMAL
 			{
-				if exp_buffer.len() >= EXP_REP_SIZE
+				if exp_buffer.len() >= self.config.exp_rep_size
 				{
 					exp_buffer.remove(0); //remove first element
 				}
@@ -379,28 +458,56 @@ impl Player for PlayerAIQ
 		self.lr = self.get_lr();
 		self.exploration = self.get_exploration();
 		self.memreward = REW_FLAG; //reset
-		if self.games_played % TARGET_UPDATE == 0
+		if self.games_played % self.config.target_update == 0
 		{
 			self.targetnn = self.nn.clone();
 		}
 	}
+	
+	/// writes the model file (nothing to do for fixed players, they do not learn)
+	fn save(&mut self) -> Result<(), Error>
+	{
+		if !self.initialized || self.fixed { return Ok(()); }
+		let filename = self.filename.clone();
+		self.checkpoint(&filename)?;
+		self.games_saved = self.games_played;
+		Ok(())
+	}
+	
+	fn checkpoint(&self, path:&str) -> Result<(), Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		model::save(path, self.games_played, self.nn.as_ref().unwrap(), &json::encode(self.exp_buffer.as_ref().unwrap()).unwrap())
+	}
+}
+
+impl Analyze for PlayerAIQ
+{
+	/// Q-values of the legal moves (0 to 1), without exploration and learning
+	fn analyze(&mut self, field:&Field) -> Result<Vec<(u32, f64)>, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		let p = field.get_player_to_move();
+		let startp = if field.get_first_player() == p { 1.0 } else { -1.0 };
+		let state = PlayerAIQ::field_to_input(field, p, startp);
+		let qval = self.nn.as_ref().unwrap().run(&state);
+		Ok((0..field.get_w()).filter(|&x| field.is_valid_play(x)).map(|x| (x, qval[x as usize])).collect())
+	}
 }
 
 impl Drop for PlayerAIQ
 {
 	fn drop(&mut self)
 	{
-		//write neural net to file, if it was allowed to learn and was initialized
-		if self.initialized && !self.fixed
+		//write neural net to file, if it was allowed to learn, was initialized and played since the last save
+		if self.initialized && !self.fixed && self.games_played != self.games_saved
 		{
-			let file = File::create(&self.filename);
-			if file.is_err() { println!("Warning: Could not write AIQ NN file!"); return; }
-			let mut writer = BufWriter::new(file.unwrap());
-			
-			let res1 = writeln!(&mut writer, "{}", self.games_played);
-			let res2 = writeln!(&mut writer, "{}", self.nn.as_mut().unwrap().to_json());
-			let res3 = write!(&mut writer, "{}", json::encode(self.exp_buffer.as_mut().unwrap()).unwrap());
-			if res1.is_err() || res2.is_err() || res3.is_err() { println!("Warning: There was an error while writing AIQ NN file!"); return; }
+			if let Err(err) = self.save()
+			{
+				println!("Warning: Could not write AIQ NN file: {}", err);
+			}
 		}
 	}
 }
@@ -4,14 +4,16 @@
 extern crate rand;
 extern crate nn;
 
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::io::prelude::*;
 use self::rand::Rng;
 use self::nn::{NN, HaltCondition, Activation};
 use super::Player;
-use super::super::field::Field;
+use super::model;
+use super::super::config::{parse_value, parse_list, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState, Cell};
 
+//defaults of the settings
 const GAMMA:f64 = 0.95; //temporal sureness (->1 means more sure about early actions always lead to win)
 const LR:f64 = 0.1; //neural net learning rate
 const LR_DECAY:f64 = 10000f64; //NN learning rate decrease (half every DECAY games)
@@ -23,14 +25,67 @@ const RND_PICK_DEC:f64 = 20000f64; //random exploration decrease (half every DEC
 const LEARNING_SET:i32 = 100; //number of games to collect before learning
 
 
+/// settings of the AIQOff player
+#[derive(Debug, Clone, PartialEq)]
+pub struct AIQOffConfig
+{
+	pub gamma: f64, //temporal sureness
+	pub lr: f64, //neural net learning rate
+	pub lr_decay: f64, //learning rate decrease (half every lr_decay games)
+	pub lr_min: f64, //minimum learning rate
+	pub momentum: f64, //neural net momentum
+	pub epochs: u32, //epochs to learn from each turn/game
+	pub rnd_pick_start: f64, //exploration factor start
+	pub rnd_pick_dec: f64, //random exploration decrease (half every rnd_pick_dec games)
+	pub learning_set: i32, //number of games to collect before learning
+	pub layers: Vec<u32>, //hidden layer sizes of a new neural net, empty: 4n, 2n, n, n, n/2 for n squares
+	pub model: String, //file of the model, empty: AIQOff-<rules>.NN (like AIQOff-7x6.NN) in the models directory
+}
+
+impl Default for AIQOffConfig
+{
+	fn default() -> AIQOffConfig
+	{
+		AIQOffConfig { gamma: GAMMA, lr: LR, lr_decay: LR_DECAY, lr_min: LR_MIN, momentum: MOM, epochs: EPOCHS_PER_STEP,
+				rnd_pick_start: RND_PICK_START, rnd_pick_dec: RND_PICK_DEC, learning_set: LEARNING_SET, layers: Vec::new(), model: String::new() }
+	}
+}
+
+impl AIQOffConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"gamma" => self.gamma = parse_value(key, value)?,
+			"lr" => self.lr = parse_value(key, value)?,
+			"lr_decay" => self.lr_decay = parse_value(key, value)?,
+			"lr_min" => self.lr_min = parse_value(key, value)?,
+			"momentum" => self.momentum = parse_value(key, value)?,
+			"epochs" => self.epochs = parse_value(key, value)?,
+			"rnd_pick_start" => self.rnd_pick_start = parse_value(key, value)?,
+			"rnd_pick_dec" => self.rnd_pick_dec = parse_value(key, value)?,
+			"learning_set" => self.learning_set = parse_value(key, value)?,
+			"layers" => self.layers = parse_list(key, value)?,
+			"model" => self.model = value.trim().to_string(),
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
 pub struct PlayerAIQOff
 {
 	initialized: bool,
 	fixed: bool, //should the agent learn or not (fixed => dont learn)
 	filename: String,
-	pid: i32, //player ID
+	model_dir: String, //directory of the model file, if no file is chosen in the settings (empty: current directory)
+	pid: PlayerId, //player ID
+	config: AIQOffConfig,
 	nn: Option<NN>, //neural network
 	games_played: u32,
+	games_saved: u32, //games played when the model file was written last
 	lr: f64,
 	exploration: f64,
 	play_buffer: Vec<(Vec<f64>, Vec<f64>)>,
@@ -41,19 +96,30 @@ impl PlayerAIQOff
 {
 	pub fn new(fix:bool) -> Box<PlayerAIQOff>
 	{
-		Box::new(PlayerAIQOff { initialized: false, fixed: fix, filename: String::new(), pid: 0,
-				nn: None, games_played: 0, lr: LR, exploration: RND_PICK_START,
+		PlayerAIQOff::with_config(fix, AIQOffConfig::default())
+	}
+	
+	pub fn with_config(fix:bool, config:AIQOffConfig) -> Box<PlayerAIQOff>
+	{
+		Box::new(PlayerAIQOff { initialized: false, fixed: fix, filename: String::new(), model_dir: String::new(), pid: PlayerId::P1,
+				nn: None, games_played: 0, games_saved: 0, lr: config.lr, exploration: config.rnd_pick_start, config,
 				play_buffer: Vec::new(), num_buffered: 0 })
 	}
 	
+	/// directory of the model file, unless the model setting chooses a file
+	pub fn set_model_dir(&mut self, dir:&str)
+	{
+		self.model_dir = dir.to_string();
+	}
+	
 	fn get_exploration(&self) -> f64
 	{
-		RND_PICK_START * (2f64).powf(-(self.games_played as f64)/RND_PICK_DEC)
+		self.config.rnd_pick_start * (2f64).powf(-(self.games_played as f64)/self.config.rnd_pick_dec)
 	}
 	
 	fn get_lr(&self) -> f64
 	{
-		LR_MIN.max(LR * (2f64).powf(-(self.games_played as f64)/LR_DECAY))
+		self.config.lr_min.max(self.config.lr * (2f64).powf(-(self.games_played as f64)/self.config.lr_decay))
 	}
 	
 	fn argmax(slice:&[f64]) -> u32
@@ -71,29 +137,32 @@ impl PlayerAIQOff
 		x
 	}
 	
-	fn field_to_input(field:&mut Field, p:i32) -> Vec<f64>
+	fn field_to_input(field:&mut Field, p:PlayerId) -> Vec<f64>
 	{
-		let op:i32 = if p == 1 { 2 } else { 1 }; //other player
 		let mut input:Vec<f64> = Vec::with_capacity((2*field.get_size() + field.get_w()) as usize);
-		for (i, val) in field.get_field().iter().enumerate()
+		let cells = field.get_field();
+		for (i, val) in cells.iter().enumerate()
 		{ //2 nodes for every square: -1 enemy, 0 free, 1 own; 0 square will not be reached with one move, 1 square can be directly filled
-			if *val == p { input.push(1f64); input.push(0f64); }
-			else if *val == op { input.push(-1f64); input.push(0f64); }
-			else
-			{ //empty square
-				input.push(0f64);
-				if (i as u32) < (field.get_size()-field.get_w()) { input.push(if field.get_field()[i+field.get_w() as usize] != 0 { 1f64 } else { 0f64 }); }
-				else { input.push(1f64); }
+			match *val
+			{
+				Cell::Stone(q) if q == p => { input.push(1f64); input.push(0f64); },
+				Cell::Stone(_) => { input.push(-1f64); input.push(0f64); },
+				Cell::Empty =>
+					{ //empty square
+						input.push(0f64);
+						if (i as u32) < (field.get_size()-field.get_w()) { input.push(if cells[i+field.get_w() as usize] != Cell::Empty { 1f64 } else { 0f64 }); }
+						else { input.push(1f64); }
+					},
 			}
 		}
 		for x in 0..field.get_w()
 		{ //1 node for every column: 1 own win, -1 enemy win, 0 none (which consistent order of the nodes does not matter, fully connected)
-			if field.play(p, x)
+			if field.play(p, x).is_ok()
 			{ //valid play
 				match field.get_state()
 				{
-					-1 | 0 => input.push(0f64),
-					pid => input.push(if pid == p {1f64} else {-1f64}),
+					GameState::Win(pid) => input.push(if pid == p {1f64} else {-1f64}),
+					_ => input.push(0f64),
 				}
 				field.undo();
 			}
@@ -106,10 +175,10 @@ impl PlayerAIQOff
 	{
 		let nn = self.nn.as_mut().unwrap();
 		nn.train(&self.play_buffer)
-					.halt_condition(HaltCondition::Epochs(EPOCHS_PER_STEP))
+					.halt_condition(HaltCondition::Epochs(self.config.epochs))
 					.log_interval(None)
 					//.log_interval(Some(2)) //debug
-					.momentum(MOM)
+					.momentum(self.config.momentum)
 					.rate(self.lr)
 					.go();
 		self.play_buffer.clear();
@@ -119,74 +188,74 @@ impl PlayerAIQOff
 
 impl Player for PlayerAIQOff
 {
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	#[allow(unused_variables)]
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
 		self.pid = p;
 		
-		self.filename = format!("AIQOff-{}x{}.NN", field.get_w(), field.get_h());
-		let file = File::open(&self.filename);
-		if file.is_err()
-		{
-			//create new neural net, is it could not be loaded
-			let n = field.get_size();
-			let w = field.get_w();
-			self.nn = Some(NN::new(&[2*n+w, 4*n, 2*n, n, n, n/2, w], Activation::Sigmoid, Activation::Sigmoid)); //set size of NN layers here
-			//games_played, exploration, lr already set
-		}
-		else
+		self.filename = model::path(&self.config.model, &self.model_dir, &format!("AIQOff-{}.NN", field.get_rules_tag()));
+		match model::load(&self.filename)?
 		{
-			//load neural net from file (and games played)
-			let mut reader = BufReader::new(file.unwrap());
-			let mut datas = String::new();
-			let mut nns = String::new();
-			
-			let res1 = reader.read_line(&mut datas);
-			let res2 = reader.read_to_string(&mut nns);
-			if res1.is_err() || res2.is_err() { return false; }
-			
-			let res = datas.trim().parse::<u32>();
-			if res.is_err() { return false; }
-			self.games_played = res.unwrap();
-			self.nn = Some(NN::from_json(&nns));
-			
-			self.lr = self.get_lr();
-			self.exploration = self.get_exploration();
+			None =>
+				{
+					//create new neural net, as there is no file yet
+					let n = field.get_size();
+					let w = field.get_w();
+					let mut layers = vec![2*n+w];
+					if self.config.layers.is_empty() { layers.extend_from_slice(&[4*n, 2*n, n, n, n/2]); } else { layers.extend_from_slice(&self.config.layers); }
+					layers.push(w);
+					self.nn = Some(NN::new(&layers, Activation::Sigmoid, Activation::Sigmoid));
+					//games_played, exploration, lr already set
+				},
+			Some(model) =>
+				{
+					//neural net from file (and games played)
+					self.games_played = model.games_played;
+					self.nn = Some(model.nn);
+					self.lr = self.get_lr();
+					self.exploration = self.get_exploration();
+				},
 		}
 		
+		self.games_saved = self.games_played;
 		self.initialized = true;
-		true
+		Ok(())
 	}
 	
 	#[allow(unused_variables)]
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
 		//nothing
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	#[allow(unused_variables)]
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
+		if !self.initialized { return Err(Error::NotInitialized); }
 		//variables
 		let mut rng = rand::thread_rng();
 		let nn = self.nn.as_mut().unwrap();
 		let mut res = false;
+		let mut x = 0;
+		let mut field = field.clone(); //the input tries the moves on it
 		
 		//choose an action (try again until it meets the rules)
 		while !res
 		{
+			if field.get_state().is_over() { return Err(Error::GameOver); }
 			//get current state formatted for the neural net (in loop because ownerships gets moved later)
-			let state = PlayerAIQOff::field_to_input(field, self.pid);
+			let state = PlayerAIQOff::field_to_input(&mut field, self.pid);
 			
 			//choose action by e-greedy
 			let mut qval = nn.run(&state);
-			let mut x = PlayerAIQOff::argmax(&qval);
+			x = PlayerAIQOff::argmax(&qval);
 			if rng.gen::<f64>() < self.exploration //random exploration
 			{
 				x = rng.gen::<u32>() % field.get_w();
 			}
 			
-			//perform action
-			res = field.play(self.pid, x);
+			//check action
+			res = field.is_valid_play(x);
 			
 			//save play data if not fixed, but learn if move did not was rule-conform
 			if !self.fixed || !res
@@ -208,26 +277,28 @@ impl Player for PlayerAIQOff
 						.halt_condition(HaltCondition::Epochs(1)) //only one epoch for rule mistakes
 						.log_interval(None)
 						//.log_interval(Some(2)) //debug
-						.momentum(MOM)
+						.momentum(self.config.momentum)
 						.rate(self.lr)
 						.go();
 				}
 			}
 		}
 		//field.print(); //debug
-		res
+		Ok(x)
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		if self.initialized && !self.fixed //learning
 		{
 			//get reward
-			let otherp = if self.pid == 1 {2} else {1};
-			let mut reward = 0f64; //draw (or running, should not happen)
-			if state == self.pid { reward = 1f64; }
-			else if state == otherp { reward = -1f64; }
+			let reward = match state.winner()
+				{
+					Some(p) if p == self.pid => 1f64,
+					Some(_) => -1f64,
+					None => 0f64, //draw (or running, should not happen)
+				};
 			
 			//compute learning data
 			let w:usize = field.get_w() as usize;
@@ -241,7 +312,7 @@ impl Player for PlayerAIQOff
 				{
 					if qval[i] == -1f64
 					{
-						qval[i] = (GAMMA.powi(len - count) * reward + 1f64) / 2f64; // (+1)/2 => accumulate for sigmoid
+						qval[i] = (self.config.gamma.powi(len - count) * reward + 1f64) / 2f64; // (+1)/2 => accumulate for sigmoid
 						break;
 					}
 				}
@@ -249,33 +320,43 @@ impl Player for PlayerAIQOff
 			
 			//learn
 			self.num_buffered += 1;
-			if self.num_buffered >= LEARNING_SET { self.learn(); }
+			if self.num_buffered >= self.config.learning_set { self.learn(); }
 		}
 		//set parameters
 		self.games_played += 1;
 		self.lr = self.get_lr();
 		self.exploration = self.get_exploration();
 	}
+	
+	/// learns from the remaining data and writes the model file (nothing to do for fixed players, they do not learn)
+	fn save(&mut self) -> Result<(), Error>
+	{
+		if !self.initialized || self.fixed { return Ok(()); }
+		if self.num_buffered > 0 { self.learn(); } //learn from the games since the last learning first
+		let filename = self.filename.clone();
+		self.checkpoint(&filename)?;
+		self.games_saved = self.games_played;
+		Ok(())
+	}
+	
+	fn checkpoint(&self, path:&str) -> Result<(), Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		model::save(path, self.games_played, self.nn.as_ref().unwrap(), "")
+	}
 }
 
 impl Drop for PlayerAIQOff
 {
 	fn drop(&mut self)
 	{
-		//write neural net to file, if it may has learned and was initialized
-		if self.initialized && !self.fixed
+		//learn and write neural net to file, if it was allowed to learn, was initialized and has unsaved games or data to learn from
+		if self.initialized && !self.fixed && (self.games_played != self.games_saved || self.num_buffered > 0)
 		{
-			//learn
-			if self.num_buffered > 0 { self.learn(); }
-			
-			//save NN
-			let file = File::create(&self.filename);
-			if file.is_err() { println!("Warning: Could not write AIQ NN file!"); return; }
-			let mut writer = BufWriter::new(file.unwrap());
-			
-			let res1 = writeln!(&mut writer, "{}", self.games_played);
-			let res2 = write!(&mut writer, "{}", self.nn.as_mut().unwrap().to_json());
-			if res1.is_err() || res2.is_err() { println!("Warning: There was an error while writing AIQ NN file!"); return; }
+			if let Err(err) = self.save()
+			{
+				println!("Warning: Could not write AIQOff NN file: {}", err);
+			}
 		}
 	}
 }
@@ -6,15 +6,19 @@ extern crate nn;
 extern crate rustc_serialize;
 
 use self::rustc_serialize::json;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::io::prelude::*;
 use self::rand::Rng;
 use self::nn::{NN, HaltCondition, Activation};
-use super::Player;
-use super::super::field::Field;
+use super::{Player, Analyze, SearchInfo, Counters, is_timed_out, principal_variation};
+use super::model;
+use super::transposition::{TransTable, Bound, TT_MEMORY, value_to_tt, value_from_tt};
+use super::super::config::{parse_value, parse_list, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState, Cell};
 use std::f64;
+use std::time::{Duration, Instant};
 
+//defaults of the settings
 const DEEPNESS:u32 = 5; //recursion limit
 const LEARN_FREQ:u32 = 10; //number of games between learning to collect data to train with
 const KEEP_NUM:usize = 0; //number of state value pairs to save between learning episodes
@@ -34,58 +38,144 @@ const VAL_WIN:f64 = 0.9; //starting player wins value for NN learning (greater t
 const VAL_DRAW:f64 = 0.0; //draw's value for NN learning (0!)
 const VAL_LOSE:f64 = -0.9; //starting player loses value for NN learning (less than 0!)
 
+
+/// settings of the AIValue player
+#[derive(Debug, Clone, PartialEq)]
+pub struct AIValueConfig
+{
+	pub depth: u32, //recursion limit
+	pub tt_memory: usize, //bytes of the transposition table (0 disables it)
+	pub learn_freq: u32, //number of games between learning
+	pub keep_num: usize, //number of state value pairs to save between learning episodes
+	pub gamma: f64, //temporal unsureness factor
+	pub lr: f64, //neural net learning rate
+	pub lr_decay: f64, //learning rate decrease per game
+	pub lr_min: f64, //minimum learning rate
+	pub lambda: f64, //L2 regularization parameter
+	pub momentum: f64, //neural net momentum
+	pub epochs: u32, //training epochs per data set
+	pub layers: Vec<u32>, //hidden layer sizes of a new neural net, empty: 3n, n for n squares
+	pub model: String, //file of the model, empty: AIValue-<rules>.NN (like AIValue-7x6.NN) in the models directory
+}
+
+impl Default for AIValueConfig
+{
+	fn default() -> AIValueConfig
+	{
+		AIValueConfig { depth: DEEPNESS, tt_memory: TT_MEMORY, learn_freq: LEARN_FREQ, keep_num: KEEP_NUM, gamma: GAMMA, lr: LR, lr_decay: LR_DECAY,
+				lr_min: LR_MIN, lambda: LAMBDA, momentum: MOM, epochs: EPOCHS, layers: Vec::new(), model: String::new() }
+	}
+}
+
+impl AIValueConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"depth" => self.depth = parse_value(key, value)?,
+			"tt_memory" => self.tt_memory = parse_value(key, value)?,
+			"learn_freq" => self.learn_freq = parse_value(key, value)?,
+			"keep_num" => self.keep_num = parse_value(key, value)?,
+			"gamma" => self.gamma = parse_value(key, value)?,
+			"lr" => self.lr = parse_value(key, value)?,
+			"lr_decay" => self.lr_decay = parse_value(key, value)?,
+			"lr_min" => self.lr_min = parse_value(key, value)?,
+			"lambda" => self.lambda = parse_value(key, value)?,
+			"momentum" => self.momentum = parse_value(key, value)?,
+			"epochs" => self.epochs = parse_value(key, value)?,
+			"layers" => self.layers = parse_list(key, value)?,
+			"model" => self.model = value.trim().to_string(),
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
 pub struct PlayerAIValue
 {
 	initialized: bool,
 	fixed: bool, //fixed agent? (don't learn)
-	pid: i32, //player ID
-	startp: i32, //starting player
+	pid: PlayerId, //player ID
+	startp: PlayerId, //starting player
 	games_played: u32, //number of games the agent played
+	games_saved: u32, //games played when the model file was written last
 	filename: String, //file name for NN/agent information
+	model_dir: String, //directory of the model file, if no file is chosen in the settings (empty: current directory)
 	nn: Option<NN>, //neural network for neutral state evaluation (value based on starting player)
 	lr: f64, //NN learning rate
 	current_game: Vec<Vec<f64>>, //buffer for states, that occured in the current game
 	games_buffer: Vec<(Vec<f64>,Vec<f64>)>, //buffer of game data to learn -> training buffer
+	config: AIValueConfig,
+	time_limit: Option<Duration>, //time per move, searches deeper and deeper until it is over (depth is not used then)
+	tt: TransTable, //values of searched positions from the view of this player, cleared every game
+	info: Option<SearchInfo>, //search of the last move
 }
 
 impl PlayerAIValue
 {
 	pub fn new(fix: bool) -> Box<PlayerAIValue>
 	{
-		Box::new(PlayerAIValue { initialized: false, fixed: fix, pid: 0, startp: 0, games_played: 0,
-						filename: String::new(), nn: None, lr: LR, current_game: Vec::new(),
-						games_buffer: Vec::new() })
+		PlayerAIValue::with_config(fix, AIValueConfig::default())
+	}
+	
+	pub fn with_config(fix: bool, config:AIValueConfig) -> Box<PlayerAIValue>
+	{
+		Box::new(PlayerAIValue { initialized: false, fixed: fix, pid: PlayerId::P1, startp: PlayerId::P1, games_played: 0, games_saved: 0,
+						filename: String::new(), model_dir: String::new(), nn: None, lr: config.lr, current_game: Vec::new(),
+						games_buffer: Vec::new(), config, time_limit: None, tt: TransTable::new(0, false), info: None })
+	}
+	
+	/// directory of the model file, unless the model setting chooses a file
+	pub fn set_model_dir(&mut self, dir:&str)
+	{
+		self.model_dir = dir.to_string();
+	}
+	
+	/// sets the time to search for every move (iterative deepening), None searches to the fixed depth
+	pub fn set_time_limit(&mut self, limit:Option<Duration>)
+	{
+		self.time_limit = limit;
+	}
+	
+	/// sets the memory of the transposition table in bytes (0 disables it), has to be called before init
+	pub fn set_tt_memory(&mut self, memory:usize)
+	{
+		self.config.tt_memory = memory;
 	}
 	
 	fn get_lr(&self) -> f64
 	{
-		LR_MIN.max(LR - LR_DECAY * self.games_played as f64)
+		self.config.lr_min.max(self.config.lr - self.config.lr_decay * self.games_played as f64)
 	}
 	
 	//raw field
-	fn field_to_input(field:&mut Field, p:i32) -> Vec<f64>
+	fn field_to_input(field:&Field, p:PlayerId) -> Vec<f64>
 	{ //input: p = start player
-		let op:i32 = if p == 1 { 2 } else { 1 }; //other player
 		let mut input:Vec<f64> = Vec::with_capacity(field.get_size() as usize);
 		//1 nodes for every square: -1 enemy, 0 free, 1 own
 		for val in field.get_field().iter()
 		{
-			if *val == p { input.push(1f64); }
-			else if *val == op { input.push(-1f64); }
-			else { input.push(0f64); } //empty square
+			match *val
+			{
+				Cell::Stone(q) if q == p => input.push(1f64),
+				Cell::Stone(_) => input.push(-1f64),
+				Cell::Empty => input.push(0f64), //empty square
+			}
 		}
 		//return
 		input
 	}
 	
 	//returns value of board position: +1.0 player wins, -1.0 other player wins, 0.0 draw or even board
-	fn heur(&self, field:&mut Field, p:i32, deep:u32) -> f64 //p = player. translated from start player by (value * -1) if they are not same.
+	fn heur(&self, field:&mut Field, p:PlayerId, deep:u32) -> f64 //p = player. translated from start player by (value * -1) if they are not same.
 	{
-		let op = if p == 1 {2} else {1};
+		let op = p.other();
 		let state = field.get_state(); //return best or worst value on win/loose (neutral on tie)
-		if state == -1 { return 0.0; }
-		else if state == p { return VAL_MAX - deep as f64; }
-		else if state == op { return VAL_MIN + deep as f64; }
+		if state == GameState::Draw { return 0.0; }
+		else if state == GameState::Win(p) { return VAL_MAX - deep as f64; }
+		else if state == GameState::Win(op) { return VAL_MIN + deep as f64; }
 		else
 		{ //game running -> evaluate
 			let nn = self.nn.as_ref().unwrap();
@@ -99,32 +189,77 @@ impl PlayerAIValue
 		}
 	}
 	
-	fn minimax(&self, field:&mut Field, p:i32, deep:u32) -> f64
+	/// if the deadline is over, the search is stopped and returns a meaningless value
+	fn minimax(&self, field:&mut Field, p:PlayerId, deep:u32, depth:u32, deadline:Option<Instant>, count:&mut Counters) -> f64
 	{
-		let op = if p == 1 {2} else {1};
-		if deep > DEEPNESS { return self.heur(field, if deep%2 == 0 {op} else {p}, deep); } //leaf node -> return evaluated heuristic, mechanism to get heur always for same player
+		let op = p.other();
+		count.nodes += 1;
+		if deep > depth { return self.heur(field, if deep%2 == 0 {op} else {p}, deep); } //leaf node -> return evaluated heuristic, mechanism to get heur always for same player
 		let state = field.get_state(); //return early on game end
-		if state == -1 { return 0.0; }
-		else if state == p { return if deep%2 == 0 {VAL_MIN + deep as f64} else {VAL_MAX - deep as f64}; }
-		else if state == op { return if deep%2 == 0 {VAL_MAX - deep as f64} else {VAL_MIN + deep as f64}; }
+		if state == GameState::Draw { return 0.0; }
+		else if state == GameState::Win(p) { return if deep%2 == 0 {VAL_MIN + deep as f64} else {VAL_MAX - deep as f64}; }
+		else if state == GameState::Win(op) { return if deep%2 == 0 {VAL_MAX - deep as f64} else {VAL_MIN + deep as f64}; }
+		
+		//look up earlier searches of this position, all values are exact without pruning
+		if is_timed_out(deadline) { return 0.0; }
+		let remaining = depth + 1 - deep;
+		if self.tt.is_enabled() { count.tt_probes += 1; }
+		if let Some(entry) = self.tt.probe(field)
+		{
+			count.tt_hits += 1;
+			if entry.depth >= remaining && entry.bound == Bound::Exact { return value_from_tt(entry.value, deep); }
+		}
 		
 		//else: game running -> go deeper
 		let mut heur = if deep%2 == 0 { f64::INFINITY } else { f64::NEG_INFINITY };
+		let mut best = None;
 		for i in 0..field.get_w()
 		{
-			if field.play(p, i)
+			if field.play(p, i).is_ok()
 			{
-				let val = self.minimax(field, op, deep+1);
+				let val = self.minimax(field, op, deep+1, depth, deadline, count);
 				field.undo();
 				if (deep%2 == 0 && val < heur) || (deep%2 == 1 && val > heur) //min or max according to which player's turn it is
 				{
 					heur = val;
+					best = Some(i);
 				}
 			}
 		}
+		if is_timed_out(deadline) { return heur; } //incomplete search, do not store
+		self.tt.store(field, value_to_tt(heur, deep), remaining, Bound::Exact, best);
 		heur
 	}
 	
+	/// searches all moves to the given depth and returns the best one with the values of all moves, None if the deadline was over before finishing
+	fn search(&self, field:&mut Field, depth:u32, deadline:Option<Instant>, count:&mut Counters) -> Option<(u32, Vec<(u32, f64)>)>
+	{
+		let p = self.pid;
+		let op = p.other();
+		let mut x:u32 = 0;
+		let mut max = f64::NEG_INFINITY;
+		let mut scores = Vec::new();
+		//decide by evaluation
+		for i in 0..field.get_w()
+		{
+			let mut val = f64::NEG_INFINITY;
+			if field.play(p, i).is_ok()
+			{
+				val = self.minimax(field, op, 2, depth, deadline, count);
+				field.undo();
+				scores.push((i, val));
+			}
+			if max < val || !field.is_valid_play(x)
+			{
+				max = val;
+				x = i;
+			}
+		}
+		if is_timed_out(deadline) { return None; }
+		
+		Some((x, scores))
+	}
+	
 	fn learn_from_data(&mut self)
 	{
 		//use the collected data to improve the neural net
@@ -142,110 +277,112 @@ impl PlayerAIValue
 		
 		//learn
 		nn.train(&self.games_buffer)
-			.halt_condition(HaltCondition::Epochs(EPOCHS))
+			.halt_condition(HaltCondition::Epochs(self.config.epochs))
 			.log_interval(None)
-			.momentum(MOM)
+			.momentum(self.config.momentum)
 			.rate(self.lr)
-			.lambda(LAMBDA / (self.games_played as f64 + 1000.0))
+			.lambda(self.config.lambda / (self.games_played as f64 + 1000.0))
 			.go();
 		
 		//flush buffer
-		self.games_buffer.truncate(KEEP_NUM); //.clear();
+		self.games_buffer.truncate(self.config.keep_num); //.clear();
 	}
 }
 
 impl Player for PlayerAIValue
 {
 	#[allow(unused_variables)]
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
-		if DEEPNESS < 1 { return false; } //invalid player, could cause bugs else
+		if self.config.depth < 1 { return Err(Error::InvalidConfig("search depth has to be at least 1".to_string())); } //invalid player, could cause bugs else
+		if self.config.learn_freq < 1 { return Err(Error::InvalidConfig("learning frequency has to be at least 1".to_string())); }
 		
 		self.pid = p;
 		
-		self.filename = format!("AIValue-{}x{}.NN", field.get_w(), field.get_h());
-		let file = File::open(&self.filename);
-		if file.is_err()
-		{
-			//create new neural net, as it could not be loaded
-			let n = field.get_size();
-			self.nn = Some(NN::new(&[n, 3*n, n, 1], Activation::PELU, Activation::Tanh)); //set size of NN layers here, be careful with activation function
-			//games_played, lr already set
-		}
-		else
+		self.filename = model::path(&self.config.model, &self.model_dir, &format!("AIValue-{}.NN", field.get_rules_tag()));
+		match model::load(&self.filename)?
 		{
-			//load neural net from file (and games played)
-			let mut reader = BufReader::new(file.unwrap());
-			let mut datas = String::new();
-			let mut nns = String::new();
-			let mut exps = String::new();
-			
-			let res1 = reader.read_line(&mut datas);
-			let res2 = reader.read_line(&mut nns);
-			let res3 = reader.read_to_string(&mut exps);
-			if res1.is_err() || res2.is_err() || res3.is_err() { return false; }
-			
-			let res = datas.trim().parse::<u32>();
-			if res.is_err() { return false; }
-			self.games_played = res.unwrap();
-			self.nn = Some(NN::from_json(&nns));
-			self.games_buffer = json::decode(&exps).unwrap();
-			
-			self.lr = self.get_lr();
+			None =>
+				{
+					//create new neural net, as there is no file yet
+					let n = field.get_size();
+					let mut layers = vec![n];
+					if self.config.layers.is_empty() { layers.extend_from_slice(&[3*n, n]); } else { layers.extend_from_slice(&self.config.layers); }
+					layers.push(1);
+					self.nn = Some(NN::new(&layers, Activation::PELU, Activation::Tanh)); //be careful with activation function
+					//games_played, lr already set
+				},
+			Some(model) =>
+				{
+					//neural net from file (and games played)
+					self.games_buffer = model::decode_data(&self.filename, &model.data)?;
+					self.games_played = model.games_played;
+					self.nn = Some(model.nn);
+					self.lr = self.get_lr();
+				},
 		}
 		
+		self.tt = TransTable::new(self.config.tt_memory, false); //the net is not symmetric, mirrored positions have values of their own
+		self.games_saved = self.games_played;
 		self.initialized = true;
-		true
+		Ok(())
 	}
 	
 	#[allow(unused_variables)]
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
 		self.startp = p;
+		self.tt.clear(); //values depend on the start player and the neural net, that might have learned
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
+		if !self.initialized { return Err(Error::NotInitialized); }
 		
-		let p = self.pid;
-		let op = if p == 1 {2} else {1};
+		let mut field = field.clone(); //the search plays and takes back moves on it
 		
 		//decide which action x to take
-		let mut x:u32 = 0;
-		let mut max = f64::NEG_INFINITY;
-		//decide by evaluation
-		for i in 0..field.get_w()
-		{
-			let mut val = f64::NEG_INFINITY;
-			if field.play(p, i)
+		let start = Instant::now();
+		let mut count = Counters::default();
+		let (x, scores, depth) = match context.time_for_move(&field, self.pid, self.time_limit)
 			{
-				val = self.minimax(field, op, 2);
-				field.undo();
-			}
-			if max < val || !field.is_valid_play(x)
-			{
-				max = val;
-				x = i;
-			}
-		}
+				None =>
+					{
+						let (x, scores) = self.search(&mut field, self.config.depth, None, &mut count).unwrap(); //can not time out
+						(x, scores, self.config.depth)
+					},
+				Some(limit) =>
+					{ //iterative deepening: use the result of the last search, that finished in time
+						let deadline = start + limit;
+						let max_depth = field.get_size() - field.get_turns().len() as u32; //searching deeper than the game lasts does not change anything
+						let (mut x, mut scores) = self.search(&mut field, 1, None, &mut count).unwrap(); //always finish the first search to have a move
+						let mut finished = 1;
+						for depth in 2..max_depth+1
+						{
+							match self.search(&mut field, depth, Some(deadline), &mut count)
+							{
+								Some(result) => { x = result.0; scores = result.1; finished = depth; },
+								None => break,
+							}
+						}
+						(x, scores, finished)
+					},
+			};
+		let pv = principal_variation(&field, &self.tt, x, depth);
+		self.info = Some(count.to_info(scores, depth, start.elapsed(), pv));
 		
 		//save game state for later mapping to win/loose. only if not fixed to save memory
 		if !self.fixed
 		{
-			let state = PlayerAIValue::field_to_input(field, self.startp);
+			let state = PlayerAIValue::field_to_input(&field, self.startp);
 			self.current_game.push(state);
 		}
 		
-		//debug
-		//println!("Heur: {}", max);
-		
-		//play (actually should always be true, unless game was finished before method invocation)
-		field.play(p, x)
+		Ok(x)
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		//parameters
 		self.games_played += 1;
@@ -255,48 +392,88 @@ impl Player for PlayerAIValue
 		if !self.fixed
 		{
 			//collect data
-			let op:i32 = if self.startp == 1 { 2 } else { 1 }; //other player
 			let mut temporal_factor = 1.0;
-			let mut value = VAL_DRAW; //draw
-			if state == self.startp { value = VAL_WIN; } //win
-			else if state == op { value = VAL_LOSE; } //lose
+			let value = match state.winner()
+				{
+					Some(p) if p == self.startp => VAL_WIN, //win
+					Some(_) => VAL_LOSE, //lose
+					None => VAL_DRAW, //draw
+				};
 			
 			while !self.current_game.is_empty()
 			{
 				let state = self.current_game.pop().unwrap();
 				let result = vec![temporal_factor * value];
 				self.games_buffer.push((state, result));
-				temporal_factor *= GAMMA; //induce unsureness at the start of the game, avoid constant value learned, prioritize fast win
+				temporal_factor *= self.config.gamma; //induce unsureness at the start of the game, avoid constant value learned, prioritize fast win
 			}
 			
 			//learn if it is time
-			if self.games_played % LEARN_FREQ == 0
+			if self.games_played % self.config.learn_freq == 0
 			{
 				self.learn_from_data();
 			}
 		}
 	}
+	
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		self.info.as_ref()
+	}
+	
+	/// learns from the remaining data and writes the model file (nothing to do for fixed players, they do not learn)
+	fn save(&mut self) -> Result<(), Error>
+	{
+		if !self.initialized || self.fixed { return Ok(()); }
+		if self.games_buffer.len() > self.config.keep_num { self.learn_from_data(); } //learn from the games since the last learning first
+		let filename = self.filename.clone();
+		self.checkpoint(&filename)?;
+		self.games_saved = self.games_played;
+		Ok(())
+	}
+	
+	fn checkpoint(&self, path:&str) -> Result<(), Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		model::save(path, self.games_played, self.nn.as_ref().unwrap(), &json::encode(&self.games_buffer).unwrap())
+	}
+}
+
+impl Analyze for PlayerAIValue
+{
+	/// values of every move searched to the search depth (the time limit is not used)
+	fn analyze(&mut self, field:&Field) -> Result<Vec<(u32, f64)>, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		//search for the player to move in the game of the position, the stored values depend on both players
+		let (pid, startp) = (self.pid, self.startp);
+		self.pid = field.get_player_to_move();
+		self.startp = field.get_first_player();
+		let reset = self.pid != pid || self.startp != startp;
+		if reset { self.tt.clear(); }
+		let mut field = field.clone();
+		let depth = self.config.depth;
+		let (_, scores) = self.search(&mut field, depth, None, &mut Counters::default()).unwrap(); //can not time out
+		if reset { self.tt.clear(); }
+		self.pid = pid;
+		self.startp = startp;
+		Ok(scores)
+	}
 }
 
 impl Drop for PlayerAIValue
 {
 	fn drop(&mut self)
 	{
-		//if it was allowed to learn and was initialized:
-		if self.initialized && !self.fixed
+		//learn and write neural net to file, if it was allowed to learn, was initialized and has unsaved games or data to learn from
+		if self.initialized && !self.fixed && (self.games_played != self.games_saved || self.games_buffer.len() > self.config.keep_num)
 		{
-			//learn from remaining data
-			self.learn_from_data();
-			
-			//write neural net to file
-			let file = File::create(&self.filename);
-			if file.is_err() { println!("Warning: Could not write AIValue NN file!"); return; }
-			let mut writer = BufWriter::new(file.unwrap());
-			
-			let res1 = writeln!(&mut writer, "{}", self.games_played);
-			let res2 = writeln!(&mut writer, "{}", self.nn.as_mut().unwrap().to_json());
-			let res3 = write!(&mut writer, "{}", json::encode(&self.games_buffer).unwrap());
-			if res1.is_err() || res2.is_err() || res3.is_err() { println!("Warning: There was an error while writing AIValue NN file!"); return; }
+			if let Err(err) = self.save()
+			{
+				println!("Warning: Could not write AIValue NN file: {}", err);
+			}
 		}
 	}
 }
@@ -0,0 +1,609 @@
+//! AlphaZero style player: neural net with policy and value outputs guiding a PUCT tree search, learns from the visit counts of its searches
+#![allow(dead_code)]
+
+extern crate rand;
+extern crate nn;
+extern crate rustc_serialize;
+
+use self::rustc_serialize::json;
+use self::rand::Rng;
+use self::nn::{NN, HaltCondition, Activation};
+use super::{Player, SearchInfo, Counters, is_timed_out};
+use super::model;
+use super::super::config::{parse_value, parse_list, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState, Cell};
+use std::time::{Duration, Instant};
+
+//defaults of the settings
+pub const SIMULATIONS:u32 = 400; //searched positions per move
+pub const C_PUCT:f64 = 1.5; //weight of the prior probabilities against the values found by the search
+const SAMPLE_MOVES:usize = 8; //moves at the start of a game, that are sampled by visit count while learning (more different games)
+const NOISE:f64 = 0.25; //share of random noise in the priors of the root while learning
+const LEARN_FREQ:u32 = 10; //number of games between learning to collect data to train with
+const KEEP_NUM:usize = 0; //number of training examples to save between learning episodes
+const LR:f64 = 0.01; //neural net learning rate
+const LR_DECAY:f64 = 0.001 / 1000f64; //NN learning rate decrease per game(s)
+const LR_MIN:f64 = 0.001; //minimum NN LR
+const LAMBDA:f64 = 0.001; //L2 regularization parameter lambda (divide by n manually, pick very small > 0, like pick LAMBDA / n)
+const MOM:f64 = 0.5; //neural net momentum
+const EPOCHS:u32 = 2; //NN training epochs per data set
+
+
+/// settings of the AlphaZero player
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlphaZeroConfig
+{
+	pub simulations: u32, //searched positions per move
+	pub c_puct: f64, //weight of the prior probabilities against the values found by the search
+	pub sample_moves: usize, //moves at the start of a learning game, that are sampled by visit count
+	pub noise: f64, //share of random noise in the priors of the root while learning
+	pub learn_freq: u32, //number of games between learning
+	pub keep_num: usize, //number of training examples to save between learning episodes
+	pub lr: f64, //neural net learning rate
+	pub lr_decay: f64, //learning rate decrease per game
+	pub lr_min: f64, //minimum learning rate
+	pub lambda: f64, //L2 regularization parameter
+	pub momentum: f64, //neural net momentum
+	pub epochs: u32, //training epochs per data set
+	pub layers: Vec<u32>, //hidden layer sizes of a new neural net, empty: 3n, n for n squares
+	pub model: String, //file of the model, empty: AlphaZero-<rules>.NN (like AlphaZero-7x6.NN) in the models directory
+}
+
+impl Default for AlphaZeroConfig
+{
+	fn default() -> AlphaZeroConfig
+	{
+		AlphaZeroConfig { simulations: SIMULATIONS, c_puct: C_PUCT, sample_moves: SAMPLE_MOVES, noise: NOISE, learn_freq: LEARN_FREQ, keep_num: KEEP_NUM,
+				lr: LR, lr_decay: LR_DECAY, lr_min: LR_MIN, lambda: LAMBDA, momentum: MOM, epochs: EPOCHS, layers: Vec::new(), model: String::new() }
+	}
+}
+
+impl AlphaZeroConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"simulations" => self.simulations = parse_value(key, value)?,
+			"c_puct" => self.c_puct = parse_value(key, value)?,
+			"sample_moves" => self.sample_moves = parse_value(key, value)?,
+			"noise" => self.noise = parse_value(key, value)?,
+			"learn_freq" => self.learn_freq = parse_value(key, value)?,
+			"keep_num" => self.keep_num = parse_value(key, value)?,
+			"lr" => self.lr = parse_value(key, value)?,
+			"lr_decay" => self.lr_decay = parse_value(key, value)?,
+			"lr_min" => self.lr_min = parse_value(key, value)?,
+			"lambda" => self.lambda = parse_value(key, value)?,
+			"momentum" => self.momentum = parse_value(key, value)?,
+			"epochs" => self.epochs = parse_value(key, value)?,
+			"layers" => self.layers = parse_list(key, value)?,
+			"model" => self.model = value.trim().to_string(),
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
+/// node of the search tree, stored in a list and linked by indices
+struct Node
+{
+	parent: Option<usize>,
+	column: u32, //move leading to this node
+	player: PlayerId, //player who did this move
+	prior: f64, //probability of the move according to the policy output
+	children: Vec<usize>,
+	expanded: bool,
+	visits: u32,
+	value: f64, //sum of the values for the player of the move (-1 loss, 0 draw, 1 win)
+}
+
+pub struct PlayerAlphaZero
+{
+	initialized: bool,
+	fixed: bool, //fixed agent? (don't learn)
+	pid: PlayerId, //player ID
+	games_played: u32, //number of games the agent played
+	games_saved: u32, //games played when the model file was written last
+	filename: String, //file name for NN/agent information
+	model_dir: String, //directory of the model file, if no file is chosen in the settings (empty: current directory)
+	nn: Option<NN>, //outputs: probability of every column (policy) and value of the position for the player to move (last output)
+	lr: f64, //NN learning rate
+	current_game: Vec<(Vec<f64>, Vec<f64>, PlayerId)>, //states of the current game with the visit distribution of the search and the player to move
+	games_buffer: Vec<(Vec<f64>,Vec<f64>)>, //buffer of game data to learn -> training buffer
+	config: AlphaZeroConfig,
+	time_limit: Option<Duration>, //time per move, searches until it is over (simulations are not used then)
+	info: Option<SearchInfo>, //search of the last move
+}
+
+impl PlayerAlphaZero
+{
+	pub fn new(fix: bool) -> Box<PlayerAlphaZero>
+	{
+		PlayerAlphaZero::with_config(fix, AlphaZeroConfig::default())
+	}
+	
+	pub fn with_config(fix: bool, config:AlphaZeroConfig) -> Box<PlayerAlphaZero>
+	{
+		Box::new(PlayerAlphaZero { initialized: false, fixed: fix, pid: PlayerId::P1, games_played: 0, games_saved: 0, filename: String::new(), model_dir: String::new(), nn: None,
+						lr: config.lr, current_game: Vec::new(), games_buffer: Vec::new(), config, time_limit: None, info: None })
+	}
+	
+	/// directory of the model file, unless the model setting chooses a file
+	pub fn set_model_dir(&mut self, dir:&str)
+	{
+		self.model_dir = dir.to_string();
+	}
+	
+	/// sets the number of searched positions per move
+	pub fn set_simulations(&mut self, simulations:u32)
+	{
+		self.config.simulations = simulations;
+	}
+	
+	/// sets the time to search for every move, None searches the fixed number of positions
+	pub fn set_time_limit(&mut self, limit:Option<Duration>)
+	{
+		self.time_limit = limit;
+	}
+	
+	/// sets the exploration constant of PUCT, higher values follow the policy output longer
+	pub fn set_c_puct(&mut self, c_puct:f64)
+	{
+		self.config.c_puct = c_puct;
+	}
+	
+	fn get_lr(&self) -> f64
+	{
+		self.config.lr_min.max(self.config.lr - self.config.lr_decay * self.games_played as f64)
+	}
+	
+	//raw field
+	fn field_to_input(field:&Field, p:PlayerId) -> Vec<f64>
+	{ //input: p = player to move
+		let mut input:Vec<f64> = Vec::with_capacity(field.get_size() as usize);
+		//1 nodes for every square: -1 enemy, 0 free, 1 own
+		for val in field.get_field().iter()
+		{
+			match *val
+			{
+				Cell::Stone(q) if q == p => input.push(1f64),
+				Cell::Stone(_) => input.push(-1f64),
+				Cell::Empty => input.push(0f64), //empty square
+			}
+		}
+		//return
+		input
+	}
+	
+	/// adds the children of a node with the priors of the neural net, returns the value of the position for the player to move
+	fn expand(&self, field:&Field, tree:&mut Vec<Node>, node:usize) -> f64
+	{
+		let p = field.get_player_to_move();
+		let output = self.nn.as_ref().unwrap().run(&PlayerAlphaZero::field_to_input(field, p));
+		let w = field.get_w();
+		let moves:Vec<u32> = (0..w).filter(|&x| field.is_valid_play(x)).collect();
+		let sum:f64 = moves.iter().map(|&x| output[x as usize]).sum();
+		for &x in moves.iter()
+		{
+			let prior = if sum > 0.0 { output[x as usize] / sum } else { 1.0 / moves.len() as f64 };
+			tree.push(Node { parent: Some(node), column: x, player: p, prior, children: Vec::new(), expanded: false, visits: 0, value: 0.0 });
+			let index = tree.len() - 1;
+			tree[node].children.push(index);
+		}
+		tree[node].expanded = true;
+		2.0 * output[w as usize] - 1.0 //sigmoid output to [-1, 1]
+	}
+	
+	/// mixes random noise into the priors of the root's children, so that learning explores other moves
+	fn add_noise(tree:&mut [Node], share:f64)
+	{
+		let mut rng = rand::thread_rng();
+		let noise:Vec<f64> = tree[0].children.iter().map(|_| rng.gen::<f64>()).collect();
+		let sum:f64 = noise.iter().sum();
+		if sum <= 0.0 { return; }
+		for (i, &child) in tree[0].children.clone().iter().enumerate()
+		{
+			tree[child].prior = (1.0 - share) * tree[child].prior + share * noise[i] / sum;
+		}
+	}
+	
+	/// child with the highest PUCT value
+	fn select(&self, tree:&[Node], node:usize) -> usize
+	{
+		let sqrt_visits = (tree[node].visits as f64).max(1.0).sqrt();
+		let mut best = tree[node].children[0];
+		let mut max = f64::NEG_INFINITY;
+		for &child in tree[node].children.iter()
+		{
+			let child_node = &tree[child];
+			let q = if child_node.visits > 0 { child_node.value / child_node.visits as f64 } else { 0.0 };
+			let puct = q + self.config.c_puct * child_node.prior * sqrt_visits / (1.0 + child_node.visits as f64);
+			if puct > max
+			{
+				max = puct;
+				best = child;
+			}
+		}
+		best
+	}
+	
+	/// one simulation: descend to a leaf, evaluate it with the neural net and back up the value. the field is restored afterwards
+	fn simulate(&self, field:&mut Field, tree:&mut Vec<Node>)
+	{
+		let mut node = 0;
+		let mut moves = 0;
+		while tree[node].expanded && !tree[node].children.is_empty()
+		{
+			node = self.select(tree, node);
+			let _ = field.play(tree[node].player, tree[node].column); //valid play, the node was created from the position
+			moves += 1;
+		}
+		//value for the player of the move leading to the leaf
+		let mut value = match field.get_state()
+			{
+				GameState::Win(_) => 1.0, //only the player of the last move can have won
+				GameState::Draw => 0.0,
+				GameState::Running => -self.expand(field, tree, node),
+			};
+		let mut current = Some(node);
+		while let Some(index) = current
+		{
+			tree[index].visits += 1;
+			tree[index].value += value;
+			value = -value; //the parent's move was done by the other player
+			current = tree[index].parent;
+		}
+		for _ in 0..moves
+		{
+			field.undo();
+		}
+	}
+	
+	/// search info of the tree after choosing x: average values of the root moves (-1 to 1) and the line of the most visited moves
+	fn tree_info(tree:&[Node], x:u32, time:Duration) -> SearchInfo
+	{
+		let mut scores:Vec<(u32, f64)> = tree[0].children.iter().filter(|&&child| tree[child].visits > 0)
+			.map(|&child| (tree[child].column, tree[child].value / tree[child].visits as f64)).collect();
+		scores.sort_by_key(|&(x, _)| x);
+		let mut pv = vec![x];
+		let mut node = tree[0].children.iter().cloned().find(|&child| tree[child].column == x);
+		while let Some(current) = node
+		{
+			node = tree[current].children.iter().cloned().filter(|&child| tree[child].visits > 0).max_by_key(|&child| tree[child].visits);
+			if let Some(child) = node { pv.push(tree[child].column); }
+		}
+		let count = Counters { nodes: tree[0].visits as u64, ..Counters::default() };
+		count.to_info(scores, pv.len() as u32, time, pv)
+	}
+	
+	/// searches the position (for the given time, the number of simulations if None) and returns the chosen column, saves the visit distribution as training target if learning
+	fn think(&mut self, field:&mut Field, limit:Option<Duration>) -> u32
+	{
+		let start = Instant::now();
+		let p = self.pid; //the empty field does not know the start player
+		let mut tree = vec![Node { parent: None, column: 0, player: p.other(), prior: 1.0, children: Vec::new(), expanded: false, visits: 0, value: 0.0 }];
+		self.expand(field, &mut tree, 0);
+		if !self.fixed { PlayerAlphaZero::add_noise(&mut tree, self.config.noise); }
+		match limit
+		{
+			None => for _ in 0..self.config.simulations { self.simulate(field, &mut tree); },
+			Some(limit) =>
+				{
+					let deadline = Some(start + limit);
+					self.simulate(field, &mut tree); //always do one simulation
+					while !is_timed_out(deadline)
+					{
+						self.simulate(field, &mut tree);
+					}
+				},
+		}
+		
+		let mut visits = vec![0.0; field.get_w() as usize];
+		for &child in tree[0].children.iter()
+		{
+			visits[tree[child].column as usize] = tree[child].visits as f64;
+		}
+		let total:f64 = visits.iter().sum();
+		let policy:Vec<f64> = visits.iter().map(|&v| v / total).collect();
+		
+		//choose the most visited move, sample by visits at the start of learning games
+		let mut x = 0;
+		for (i, &v) in visits.iter().enumerate()
+		{
+			if v > visits[x as usize] { x = i as u32; }
+		}
+		if !self.fixed && field.get_turns().len() < self.config.sample_moves
+		{
+			let mut rest = rand::thread_rng().gen::<f64>() * total;
+			for (i, &v) in visits.iter().enumerate()
+			{
+				if v > 0.0 && rest < v { x = i as u32; break; }
+				rest -= v;
+			}
+		}
+		self.info = Some(PlayerAlphaZero::tree_info(&tree, x, start.elapsed()));
+		
+		//save game state for later mapping to win/loose. only if not fixed to save memory
+		if !self.fixed
+		{
+			self.current_game.push((PlayerAlphaZero::field_to_input(field, p), policy, p));
+		}
+		x
+	}
+	
+	/// plays num games against itself, starting from the given position, and learns from them like from other games
+	pub fn self_play(&mut self, field:&Field, num:u32) -> Result<(), Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if self.fixed { return Err(Error::InvalidConfig("a fixed player does not learn from self-play".to_string())); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		let pid = self.pid;
+		let mut context = GameContext::new(field);
+		context.games = num;
+		context.players = ["self-play".to_string(), "self-play".to_string()];
+		context.training = true;
+		for i in 0..num
+		{
+			let mut game = field.clone();
+			while !game.get_state().is_over()
+			{
+				self.pid = game.get_player_to_move();
+				let x = self.think(&mut game, self.time_limit);
+				game.play(self.pid, x)?;
+			}
+			let state = game.get_state();
+			context.game = i;
+			context.history = game.get_turns().iter().map(|&(x, _)| x).collect();
+			self.outcome(&game, state, &context);
+		}
+		self.pid = pid;
+		Ok(())
+	}
+	
+	fn learn_from_data(&mut self)
+	{
+		//use the collected data to improve the neural net
+		let nn = self.nn.as_mut().unwrap();
+		let mut rng = rand::thread_rng();
+		
+		//shuffle data
+		let len = self.games_buffer.len();
+		for _ in 0..len
+		{ //n random O(1) operations on the buffer to shuffle
+			let i = rng.gen::<usize>() % len;
+			let item = self.games_buffer.swap_remove(i);
+			self.games_buffer.push(item);
+		}
+		
+		//learn
+		nn.train(&self.games_buffer)
+			.halt_condition(HaltCondition::Epochs(self.config.epochs))
+			.log_interval(None)
+			.momentum(self.config.momentum)
+			.rate(self.lr)
+			.lambda(self.config.lambda / (self.games_played as f64 + 1000.0))
+			.go();
+		
+		//flush buffer
+		self.games_buffer.truncate(self.config.keep_num);
+	}
+}
+
+impl Player for PlayerAlphaZero
+{
+	#[allow(unused_variables)]
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
+	{
+		if self.config.simulations < 1 { return Err(Error::InvalidConfig("simulations have to be at least 1".to_string())); }
+		if self.config.c_puct.is_nan() || self.config.c_puct < 0.0 { return Err(Error::InvalidConfig("PUCT constant must not be negative".to_string())); }
+		if self.config.learn_freq < 1 { return Err(Error::InvalidConfig("learning frequency has to be at least 1".to_string())); }
+		
+		self.pid = p;
+		
+		self.filename = model::path(&self.config.model, &self.model_dir, &format!("AlphaZero-{}.NN", field.get_rules_tag()));
+		match model::load(&self.filename)?
+		{
+			None =>
+				{
+					//create new neural net, as there is no file yet
+					let n = field.get_size();
+					let mut layers = vec![n];
+					if self.config.layers.is_empty() { layers.extend_from_slice(&[3*n, n]); } else { layers.extend_from_slice(&self.config.layers); }
+					layers.push(field.get_w()+1);
+					self.nn = Some(NN::new(&layers, Activation::PELU, Activation::Sigmoid)); //outputs have to be in [0, 1]
+					//games_played, lr already set
+				},
+			Some(model) =>
+				{
+					//neural net from file (and games played)
+					self.games_buffer = model::decode_data(&self.filename, &model.data)?;
+					self.games_played = model.games_played;
+					self.nn = Some(model.nn);
+					self.lr = self.get_lr();
+				},
+		}
+		
+		self.games_saved = self.games_played;
+		self.initialized = true;
+		Ok(())
+	}
+	
+	#[allow(unused_variables)]
+	fn startp(&mut self, p:PlayerId)
+	{
+		//nothing, the net evaluates positions for the player to move
+	}
+	
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		let limit = context.time_for_move(field, self.pid, self.time_limit);
+		Ok(self.think(&mut field.clone(), limit)) //the simulations play and take back moves on the field
+	}
+	
+	#[allow(unused_variables)]
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
+	{
+		//parameters
+		self.games_played += 1;
+		self.lr = self.get_lr();
+		
+		//collect data and learn if not fixed (to save memory and computation else)
+		if !self.fixed
+		{
+			//targets: visit distribution and the result for the player to move (sigmoid outputs: 1 win, 0.5 draw, 0 loss)
+			for (state_input, mut target, p) in self.current_game.drain(..)
+			{
+				target.push(match state.winner()
+					{
+						Some(winner) if winner == p => 1.0,
+						Some(_) => 0.0,
+						None => 0.5,
+					});
+				self.games_buffer.push((state_input, target));
+			}
+			
+			//learn if it is time
+			if self.games_played % self.config.learn_freq == 0
+			{
+				self.learn_from_data();
+			}
+		}
+	}
+	
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		self.info.as_ref()
+	}
+	
+	/// learns from the remaining data and writes the model file (nothing to do for fixed players, they do not learn)
+	fn save(&mut self) -> Result<(), Error>
+	{
+		if !self.initialized || self.fixed { return Ok(()); }
+		if self.games_buffer.len() > self.config.keep_num { self.learn_from_data(); } //learn from the games since the last learning first
+		let filename = self.filename.clone();
+		self.checkpoint(&filename)?;
+		self.games_saved = self.games_played;
+		Ok(())
+	}
+	
+	fn checkpoint(&self, path:&str) -> Result<(), Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		model::save(path, self.games_played, self.nn.as_ref().unwrap(), &json::encode(&self.games_buffer).unwrap())
+	}
+}
+
+impl Drop for PlayerAlphaZero
+{
+	fn drop(&mut self)
+	{
+		//learn and write neural net to file, if it was allowed to learn, was initialized and has unsaved games or data to learn from
+		if self.initialized && !self.fixed && (self.games_played != self.games_saved || self.games_buffer.len() > self.config.keep_num)
+		{
+			if let Err(err) = self.save()
+			{
+				println!("Warning: Could not write AlphaZero NN file: {}", err);
+			}
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{PlayerAlphaZero, AlphaZeroConfig};
+	use super::super::Player;
+	use super::super::super::context::GameContext;
+	use super::super::super::field::{Field, PlayerId, GameState};
+	use std::env;
+	use std::fs;
+	use std::path::{Path, PathBuf};
+	use std::process;
+	
+	/// empty directory for the model files of a test
+	fn model_dir(name:&str) -> PathBuf
+	{
+		let dir = env::temp_dir().join(format!("connect_four_alphazero_{}_{}", name, process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+	
+	/// player with a fresh neural net
+	fn new_player(fixed:bool, simulations:u32, dir:&Path) -> Box<PlayerAlphaZero>
+	{
+		let mut player = PlayerAlphaZero::with_config(fixed, AlphaZeroConfig { simulations, learn_freq: 1000, ..AlphaZeroConfig::default() });
+		player.set_model_dir(&dir.to_string_lossy());
+		let field = Field::new(7, 6, 4);
+		player.init(&field, PlayerId::P1, &GameContext::new(&field)).unwrap();
+		player
+	}
+	
+	#[test]
+	fn search_plays_valid_columns_and_wins()
+	{
+		let dir = model_dir("search");
+		{
+			let mut player = new_player(true, 400, &dir);
+			for &moves in ["", "111111", "111111777777"].iter()
+			{
+				let field = Field::from_moves(moves).unwrap();
+				let x = player.play(&field, &GameContext::new(&field)).unwrap();
+				assert!(field.is_valid_play(x), "moves {}: {}", moves, x);
+				let scores = &player.search_info().unwrap().scores;
+				assert!(!scores.is_empty() && scores.iter().all(|&(y, _)| field.is_valid_play(y)), "moves {}: {:?}", moves, scores);
+			}
+			
+			//won games are backed up as wins of the player, who moved
+			let field = Field::from_moves("121212").unwrap();
+			assert_eq!(player.play(&field, &GameContext::new(&field)).unwrap(), 0);
+			let score = player.search_info().unwrap().scores.iter().find(|&&(x, _)| x == 0).unwrap().1;
+			assert_eq!(score, 1.0);
+			
+			//and as losses of the other player: every move but the block loses
+			let field = Field::from_moves("12131").unwrap();
+			player.pid = PlayerId::P2;
+			assert_eq!(player.play(&field, &GameContext::new(&field)).unwrap(), 0);
+		}
+		fs::remove_dir_all(&dir).unwrap();
+	}
+	
+	#[test]
+	fn targets_are_visits_and_the_result_of_the_player_to_move()
+	{
+		let dir = model_dir("targets");
+		{
+			let mut player = new_player(false, 20, &dir);
+			for &(state, values) in [(GameState::Win(PlayerId::P1), [1.0, 0.0]), (GameState::Win(PlayerId::P2), [0.0, 1.0]), (GameState::Draw, [0.5, 0.5])].iter()
+			{
+				let mut field = Field::new(7, 6, 4);
+				for &pid in [PlayerId::P1, PlayerId::P2].iter()
+				{
+					player.pid = pid;
+					let x = player.play(&field, &GameContext::new(&field)).unwrap();
+					field.play(pid, x).unwrap();
+				}
+				
+				let known = player.games_buffer.len();
+				player.outcome(&field, state, &GameContext::new(&field));
+				let targets:Vec<&Vec<f64>> = player.games_buffer[known..].iter().map(|(_, target)| target).collect();
+				assert_eq!(targets.len(), 2);
+				for (target, &value) in targets.iter().zip(values.iter())
+				{
+					assert_eq!(target.len(), 8);
+					assert!((target[..7].iter().sum::<f64>() - 1.0).abs() < 1e-9, "{:?}", target);
+					assert_eq!(target[7], value, "{:?}", state);
+				}
+			}
+		}
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -4,42 +4,45 @@
 use std::io;
 use std::io::Write;
 use super::Player;
-use super::super::field::Field;
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState};
 
 
 pub struct PlayerIO
 {
 	initialized: bool,
-	pid: i32, //player ID
+	pid: PlayerId, //player ID
 }
 
 impl PlayerIO
 {
 	pub fn new() -> Box<PlayerIO>
 	{
-		Box::new(PlayerIO { initialized: false, pid: 0 })
+		Box::new(PlayerIO { initialized: false, pid: PlayerId::P1 })
 	}
 }
 
 impl Player for PlayerIO
 {
 	#[allow(unused_variables)]
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
 		self.initialized = true;
 		self.pid = p;
-		true
+		Ok(())
 	}
 	
 	#[allow(unused_variables)]
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
 		//nothing
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	#[allow(unused_variables)]
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
+		if !self.initialized { return Err(Error::NotInitialized); }
 		
 		field.print();
 		println!("");
@@ -82,16 +85,19 @@ impl Player for PlayerIO
 		}
 		println!("");
 		
-		field.play(self.pid, x)
+		Ok(x)
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		field.print();
 		println!("");
-		if state == -1 { println!("Draw!"); }
-		else { println!("Player {} won the game!", if state == 1 { "X" } else { "O" }); }
+		match state
+		{
+			GameState::Win(p) => println!("Player {} won the game!", p),
+			_ => println!("Draw!"),
+		}
 	}
 }
 
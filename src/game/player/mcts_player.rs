@@ -0,0 +1,358 @@
+//! Monte Carlo tree search player (UCT), plays the move that was explored most in simulated games
+#![allow(dead_code)]
+
+extern crate rand;
+
+use self::rand::{Rng, ThreadRng};
+use super::{Player, SearchInfo, Counters, is_timed_out};
+use super::super::config::{parse_value, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState};
+use std::time::{Duration, Instant};
+
+//defaults of the settings
+pub const ITERATIONS:u32 = 20000; //simulated games per move
+pub const EXPLORATION:f64 = ::std::f64::consts::SQRT_2; //UCT exploration constant
+
+
+/// how the simulated games are played to the end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollout
+{
+	Random, //uniformly random moves
+	Heuristic, //wins if possible, else blocks the opponent's win, else random
+}
+
+/// settings of the MCTS player
+#[derive(Debug, Clone, PartialEq)]
+pub struct MCTSConfig
+{
+	pub iterations: u32, //simulated games per move
+	pub exploration: f64, //weight of the exploration term of UCT
+}
+
+impl Default for MCTSConfig
+{
+	fn default() -> MCTSConfig
+	{
+		MCTSConfig { iterations: ITERATIONS, exploration: EXPLORATION }
+	}
+}
+
+impl MCTSConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"iterations" => self.iterations = parse_value(key, value)?,
+			"exploration" => self.exploration = parse_value(key, value)?,
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
+/// node of the search tree, stored in a list and linked by indices
+struct Node
+{
+	parent: Option<usize>,
+	column: u32, //move leading to this node
+	player: PlayerId, //player who did this move
+	children: Vec<usize>,
+	untried: Vec<u32>, //moves not expanded yet
+	visits: u32,
+	score: f64, //sum of the results for the player of the move (win 1, draw 0.5, loss 0)
+}
+
+pub struct PlayerMCTS
+{
+	initialized: bool,
+	pid: PlayerId, //player ID
+	config: MCTSConfig,
+	time_limit: Option<Duration>, //time per move, simulates until it is over (iterations are not used then)
+	rollout: Rollout,
+	rng: Box<ThreadRng>,
+	info: Option<SearchInfo>, //search of the last move
+}
+
+impl PlayerMCTS
+{
+	pub fn new(rollout:Rollout) -> Box<PlayerMCTS>
+	{
+		PlayerMCTS::with_config(rollout, MCTSConfig::default())
+	}
+	
+	pub fn with_config(rollout:Rollout, config:MCTSConfig) -> Box<PlayerMCTS>
+	{
+		Box::new(PlayerMCTS { initialized: false, pid: PlayerId::P1, config, time_limit: None, rollout, rng: Box::new(rand::thread_rng()), info: None })
+	}
+	
+	/// sets the number of simulated games per move
+	pub fn set_iterations(&mut self, iterations:u32)
+	{
+		self.config.iterations = iterations;
+	}
+	
+	/// sets the time to simulate for every move, None simulates the fixed number of games
+	pub fn set_time_limit(&mut self, limit:Option<Duration>)
+	{
+		self.time_limit = limit;
+	}
+	
+	/// sets the exploration constant, higher values try less promising moves more often
+	pub fn set_exploration(&mut self, exploration:f64)
+	{
+		self.config.exploration = exploration;
+	}
+	
+	fn valid_moves(field:&Field) -> Vec<u32>
+	{
+		if field.get_state().is_over() { return Vec::new(); }
+		(0..field.get_w()).filter(|&x| field.is_valid_play(x)).collect()
+	}
+	
+	fn new_node(field:&Field, parent:Option<usize>, column:u32, player:PlayerId) -> Node
+	{
+		Node { parent, column, player, children: Vec::new(), untried: PlayerMCTS::valid_moves(field), visits: 0, score: 0.0 }
+	}
+	
+	/// checks if player p could win by playing column x
+	fn is_winning_move(field:&mut Field, p:PlayerId, x:u32) -> bool
+	{
+		if field.play(p, x).is_err() { return false; }
+		let won = field.get_state() == GameState::Win(p);
+		field.undo();
+		won
+	}
+	
+	/// child with the highest UCT value
+	fn select(&self, tree:&[Node], node:usize) -> usize
+	{
+		let log_visits = (tree[node].visits as f64).ln();
+		let mut best = tree[node].children[0];
+		let mut max = -1.0;
+		for &child in tree[node].children.iter()
+		{
+			let child_node = &tree[child];
+			let uct = child_node.score / child_node.visits as f64 + self.config.exploration * (log_visits / child_node.visits as f64).sqrt();
+			if uct > max
+			{
+				max = uct;
+				best = child;
+			}
+		}
+		best
+	}
+	
+	/// plays the game to the end, returns the number of moves done
+	fn simulate(&mut self, field:&mut Field) -> u32
+	{
+		let mut moves = 0;
+		while !field.get_state().is_over()
+		{
+			let p = field.get_player_to_move();
+			let valid = PlayerMCTS::valid_moves(field);
+			let mut x = valid[self.rng.gen_range(0, valid.len())];
+			if self.rollout == Rollout::Heuristic
+			{
+				if let Some(&win) = valid.iter().find(|&&x| PlayerMCTS::is_winning_move(field, p, x)) { x = win; }
+				else if let Some(&block) = valid.iter().find(|&&x| PlayerMCTS::is_winning_move(field, p.other(), x)) { x = block; }
+			}
+			let _ = field.play(p, x); //valid play, checked above
+			moves += 1;
+		}
+		moves
+	}
+	
+	/// one iteration: selection, expansion, simulation and backpropagation. the field is restored afterwards
+	fn iterate(&mut self, field:&mut Field, tree:&mut Vec<Node>)
+	{
+		let mut node = 0;
+		let mut moves = 0;
+		//selection: descend through fully expanded nodes
+		while tree[node].untried.is_empty() && !tree[node].children.is_empty()
+		{
+			node = self.select(tree, node);
+			let _ = field.play(tree[node].player, tree[node].column); //valid play, the node was created from the position
+			moves += 1;
+		}
+		//expansion: add one of the untried moves
+		if !tree[node].untried.is_empty()
+		{
+			let i = self.rng.gen_range(0, tree[node].untried.len());
+			let x = tree[node].untried.swap_remove(i);
+			let p = field.get_player_to_move();
+			let _ = field.play(p, x); //valid play, untried moves are valid
+			moves += 1;
+			let child = PlayerMCTS::new_node(field, Some(node), x, p);
+			tree.push(child);
+			let index = tree.len() - 1;
+			tree[node].children.push(index);
+			node = index;
+		}
+		//simulation
+		moves += self.simulate(field);
+		let state = field.get_state();
+		//backpropagation
+		let mut current = Some(node);
+		while let Some(index) = current
+		{
+			let n = &mut tree[index];
+			n.visits += 1;
+			n.score += match state
+				{
+					GameState::Win(p) if p == n.player => 1.0,
+					GameState::Draw => 0.5,
+					_ => 0.0,
+				};
+			current = n.parent;
+		}
+		for _ in 0..moves
+		{
+			field.undo();
+		}
+	}
+	
+	/// search info of the tree after choosing x: win rates of the root moves and the line of the most visited moves
+	fn tree_info(tree:&[Node], x:u32, time:Duration) -> SearchInfo
+	{
+		let mut scores:Vec<(u32, f64)> = tree[0].children.iter().filter(|&&child| tree[child].visits > 0)
+			.map(|&child| (tree[child].column, tree[child].score / tree[child].visits as f64)).collect();
+		scores.sort_by_key(|&(x, _)| x);
+		let mut pv = vec![x];
+		let mut node = tree[0].children.iter().cloned().find(|&child| tree[child].column == x);
+		while let Some(current) = node
+		{
+			node = tree[current].children.iter().cloned().filter(|&child| tree[child].visits > 0).max_by_key(|&child| tree[child].visits);
+			if let Some(child) = node { pv.push(tree[child].column); }
+		}
+		let count = Counters { nodes: tree[0].visits as u64, ..Counters::default() };
+		count.to_info(scores, pv.len() as u32, time, pv)
+	}
+}
+
+impl Player for PlayerMCTS
+{
+	#[allow(unused_variables)]
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
+	{
+		if self.config.iterations < 1 { return Err(Error::InvalidConfig("iterations have to be at least 1".to_string())); }
+		if self.config.exploration.is_nan() || self.config.exploration < 0.0 { return Err(Error::InvalidConfig("exploration constant must not be negative".to_string())); }
+		self.initialized = true;
+		self.pid = p;
+		Ok(())
+	}
+	
+	#[allow(unused_variables)]
+	fn startp(&mut self, p:PlayerId)
+	{
+		//nothing
+	}
+	
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		let mut field = field.clone(); //the simulations play and take back moves on it
+		
+		let start = Instant::now();
+		let mut tree = vec![PlayerMCTS::new_node(&field, None, 0, self.pid.other())];
+		match context.time_for_move(&field, self.pid, self.time_limit)
+		{
+			None => for _ in 0..self.config.iterations { self.iterate(&mut field, &mut tree); },
+			Some(limit) =>
+				{
+					let deadline = Some(start + limit);
+					self.iterate(&mut field, &mut tree); //always do one iteration to have a move
+					while !is_timed_out(deadline)
+					{
+						self.iterate(&mut field, &mut tree);
+					}
+				},
+		}
+		
+		//play the most visited move, it is the most reliable one
+		let x = tree[0].children.iter().max_by_key(|&&child| tree[child].visits).map(|&child| tree[child].column).unwrap();
+		self.info = Some(PlayerMCTS::tree_info(&tree, x, start.elapsed()));
+		
+		Ok(x)
+	}
+	
+	#[allow(unused_variables)]
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
+	{
+		//nothing
+	}
+	
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		self.info.as_ref()
+	}
+}
+
+impl Drop for PlayerMCTS
+{
+	fn drop(&mut self)
+	{
+		//nothing to do
+	}
+}
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{PlayerMCTS, MCTSConfig, Rollout};
+	use super::super::Player;
+	use super::super::super::context::GameContext;
+	use super::super::super::error::Error;
+	use super::super::super::field::Field;
+	use std::f64;
+	
+	fn choose(moves:&str, rollout:Rollout) -> u32
+	{
+		let field = Field::from_moves(moves).unwrap();
+		let mut player = PlayerMCTS::with_config(rollout, MCTSConfig { iterations: 1000, ..MCTSConfig::default() });
+		player.init(&field, field.get_player_to_move(), &GameContext::new(&field)).unwrap();
+		let x = player.play(&field, &GameContext::new(&field)).unwrap();
+		assert!(player.search_info().unwrap().scores.iter().all(|&(y, _)| field.is_valid_play(y)));
+		x
+	}
+	
+	#[test]
+	fn takes_immediate_wins()
+	{
+		for &rollout in [Rollout::Heuristic, Rollout::Random].iter()
+		{
+			assert_eq!(choose("121212", rollout), 0);
+			let x = choose("445566", rollout); //both ends of the row win
+			assert!(x == 2 || x == 6, "{:?}: {}", rollout, x);
+		}
+	}
+	
+	#[test]
+	fn blocks_immediate_wins()
+	{
+		assert_eq!(choose("12131", Rollout::Heuristic), 0);
+		assert_eq!(choose("17273", Rollout::Heuristic), 3);
+	}
+	
+	#[test]
+	fn invalid_settings()
+	{
+		let field = Field::new(7, 6, 4);
+		for &(iterations, exploration) in [(0, 1.0), (100, -1.0), (100, f64::NAN)].iter()
+		{
+			let mut player = PlayerMCTS::with_config(Rollout::Random, MCTSConfig { iterations, exploration });
+			match player.init(&field, field.get_player_to_move(), &GameContext::new(&field))
+			{
+				Err(Error::InvalidConfig(_)) => (),
+				result => panic!("iterations {}, exploration {}: {:?}", iterations, exploration, result),
+			}
+		}
+	}
+}
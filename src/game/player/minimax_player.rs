@@ -1,37 +1,203 @@
 //! Minimax player
 #![allow(dead_code)]
 
-use super::Player;
-use super::super::field::Field;
-use std::thread;
+use super::{Player, Analyze, SearchInfo, Counters, is_timed_out, principal_variation};
+use super::pool::{self, ThreadPool};
+use super::transposition::{TransTable, Bound, TT_MEMORY, value_to_tt, value_from_tt};
+use super::super::config::{parse_value, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState, Cell};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 use std::f64;
 
-const DEEPNESS:u32 = 5; //recursion limit
+const DEEPNESS:u32 = 5; //default recursion limit
+const DIRECTIONS:[(i32, i32); 4] = [(1, 0), (1, 1), (0, 1), (-1, 1)]; //directions to count rows in
+//values of the threat heuristic
+const GOOD_THREAT:f64 = 40.0; //threat on a row of the player's parity (odd rows for the first player, even rows for the second)
+const BAD_THREAT:f64 = 15.0; //threat on a row of the other parity
+const PLAYABLE_THREAT:f64 = 20.0; //threat, that can be completed with the next move, but the opponent can block it
+const WINNING_THREAT:f64 = 1000.0; //playable threat of the player to move or two playable threats of the other player
 
 
+/// evaluation of the positions at the search horizon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heuristic
+{
+	Classic, //rows of two and three stones and free squares next to own stones
+	Threats, //open windows, playable threats and the odd/even parity of threats
+}
+
+impl FromStr for Heuristic
+{
+	type Err = String;
+	
+	fn from_str(s:&str) -> Result<Heuristic, String>
+	{
+		match &s.to_lowercase()[..]
+		{
+			"classic" => Ok(Heuristic::Classic),
+			"threats" => Ok(Heuristic::Threats),
+			_ => Err("expected classic or threats".to_string()),
+		}
+	}
+}
+
+/// settings of the minimax player
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinimaxConfig
+{
+	pub depth: u32, //recursion limit
+	pub tt_memory: usize, //bytes of the transposition table (0 disables it)
+	pub heuristic: Heuristic,
+	pub threads: usize, //searching threads, including the main one. helpers come from the shared pool and make the chosen moves depend on timing
+}
+
+impl Default for MinimaxConfig
+{
+	fn default() -> MinimaxConfig
+	{
+		MinimaxConfig { depth: DEEPNESS, tt_memory: TT_MEMORY, heuristic: Heuristic::Classic, threads: 1 }
+	}
+}
+
+impl MinimaxConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"depth" => self.depth = parse_value(key, value)?,
+			"tt_memory" => self.tt_memory = parse_value(key, value)?,
+			"heuristic" => self.heuristic = parse_value(key, value)?,
+			"threads" => self.threads = parse_value(key, value)?,
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
 pub struct PlayerMinimax
 {
 	initialized: bool,
-	pid: i32, //player ID
+	pid: PlayerId, //player ID
+	startp: PlayerId, //starting player of the game the table holds values of
+	config: MinimaxConfig,
+	time_limit: Option<Duration>, //time per move, searches deeper and deeper until it is over (depth is not used then)
+	tt: Arc<TransTable>, //shared by the search threads, values from the view of this player
+	pool: Option<&'static ThreadPool>, //shared helper threads, None searches alone
+	info: Option<SearchInfo>, //search of the last move
+}
+
+/// tells a search to stop: when the deadline is over or the flag is set (helper searches, when the main search is finished)
+#[derive(Clone)]
+struct Stop
+{
+	deadline: Option<Instant>,
+	flag: Arc<AtomicBool>,
+}
+
+impl Stop
+{
+	fn is_stopped(&self) -> bool
+	{
+		self.flag.load(Ordering::Relaxed) || is_timed_out(self.deadline)
+	}
+}
+
+/// chosen column and the values of all moves
+type Choice = (u32, Vec<(u32, f64)>);
+
+/// what one search thread uses in every node
+struct Search<'a>
+{
+	depth: u32, //recursion limit
+	order: &'a [u32], //move order of the nodes below the root
+	tt: &'a TransTable,
+	stop: &'a Stop,
+	eval: Heuristic,
+	count: Counters, //searched nodes of this thread
 }
 
 impl PlayerMinimax
 {
 	pub fn new() -> Box<PlayerMinimax>
 	{
-		Box::new(PlayerMinimax { initialized: false, pid: 0 })
+		PlayerMinimax::with_config(MinimaxConfig::default())
+	}
+	
+	/// minimax player searching depth moves ahead (including its own move)
+	pub fn with_depth(depth:u32) -> Box<PlayerMinimax>
+	{
+		PlayerMinimax::with_config(MinimaxConfig { depth, ..MinimaxConfig::default() })
+	}
+	
+	pub fn with_config(config:MinimaxConfig) -> Box<PlayerMinimax>
+	{
+		Box::new(PlayerMinimax { initialized: false, pid: PlayerId::P1, startp: PlayerId::P1, config, time_limit: None, tt: Arc::new(TransTable::new(0, true)), pool: None, info: None })
+	}
+	
+	/// sets the time to search for every move (iterative deepening), None searches to the fixed depth
+	pub fn set_time_limit(&mut self, limit:Option<Duration>)
+	{
+		self.time_limit = limit;
+	}
+	
+	/// sets the memory of the transposition table in bytes (0 disables it), has to be called before init
+	pub fn set_tt_memory(&mut self, memory:usize)
+	{
+		self.config.tt_memory = memory;
+	}
+	
+	/// columns ordered from the center to the sides, central moves are most often the best
+	fn move_order(w:u32) -> Vec<u32>
+	{
+		let mut order:Vec<u32> = (0..w).collect();
+		order.sort_by_key(|&x| ((2*x as i32 - (w as i32 - 1)).abs(), x));
+		order
 	}
 	
-	fn heur(field:&mut Field, p:i32, deep:u32) -> f64
+	/// checks if player p could win by playing column x
+	fn is_winning_move(field:&mut Field, p:PlayerId, x:u32) -> bool
 	{
-		let op = if p == 1 {2} else {1};
+		if field.play(p, x).is_err() { return false; }
+		let won = field.get_state() == GameState::Win(p);
+		field.undo();
+		won
+	}
+	
+	/// length of the row of equal stones starting at (x, y) in direction (dx, dy), counted up to the win length
+	fn row_len(field:&Field, x:u32, y:u32, dx:i32, dy:i32) -> u32
+	{
+		let val = field.get_val(x, y);
+		let mut len = 1;
+		let (mut cx, mut cy) = (x as i32 + dx, y as i32 + dy);
+		while len < field.get_win() && cx >= 0 && cy >= 0 && field.get_val(cx as u32, cy as u32) == val
+		{
+			len += 1;
+			cx += dx;
+			cy += dy;
+		}
+		len
+	}
+	
+	fn heur(field:&mut Field, p:PlayerId, deep:u32, eval:Heuristic) -> f64
+	{
+		let op = p.other();
 		let state = field.get_state(); //return best or worst value on win/loose (neutral on tie)
-		if state == -1 { return 0.0; }
-		else if state == p { return 10002.0 - deep as f64; }
-		else if state == op { return -10002.0 + deep as f64; }
+		if state == GameState::Draw { return 0.0; }
+		else if state == GameState::Win(p) { return 10002.0 - deep as f64; }
+		else if state == GameState::Win(op) { return -10002.0 + deep as f64; }
+		else if eval == Heuristic::Threats { return PlayerMinimax::threat_heur(field, p); }
 		else
 		{ //game running -> evaluate
-			//count 2 and 3 rows of player and enemy, add up free squares next to player stones
+			//count rows one and two stones short of a win (2 and 3 rows in connect four) of player and enemy, add up free squares next to player stones
+			let win = field.get_win();
 			let mut pr2 = 0;
 			let mut pr3 = 0;
 			let mut heur = 0.0;
@@ -41,63 +207,131 @@ impl PlayerMinimax
 				for x in 0..field.get_w()
 				{
 					let val = field.get_val(x, y);
-					if val == p
+					if val == Cell::Stone(p)
 					{
-						if field.get_val(x+1, y) == p { if field.get_val(x+2, y) == p { pr3 += 1; } else { pr2 += 1; } }
-						if field.get_val(x+1, y+1) == p { if field.get_val(x+2, y+2) == p { pr3 += 1; } else { pr2 += 1; } }
-						if field.get_val(x, y+1) == p { if field.get_val(x, y+2) == p { pr3 += 1; } else { pr2 += 1; } }
-						if x>=1 && field.get_val(x-1, y+1) == p { if x>=2 && field.get_val(x-2, y+2) == p { pr3 += 1; } else { pr2 += 1; } }
+						for &(dx, dy) in DIRECTIONS.iter()
+						{
+							let len = PlayerMinimax::row_len(field, x, y, dx, dy);
+							if len+1 >= win { pr3 += 1; } else if len >= 2 && len+2 == win { pr2 += 1; }
+						}
 						//free squares
 						if y>=1
 						{
-							if x>=1 && field.get_val(x-1, y-1) == 0 { heur += value; }
-							if field.get_val(x, y-1) == 0 { heur += value; }
-							if x<field.get_w()-1 && field.get_val(x+1, y-1) == 0 { heur += value; }
+							if x>=1 && field.get_val(x-1, y-1) == Cell::Empty { heur += value; }
+							if field.get_val(x, y-1) == Cell::Empty { heur += value; }
+							if x<field.get_w()-1 && field.get_val(x+1, y-1) == Cell::Empty { heur += value; }
 						}
-						if x>=1 && field.get_val(x-1, y) == 0 { heur += value; }
-						if x<field.get_w()-1 && field.get_val(x+1, y) == 0 { heur += value; }
+						if x>=1 && field.get_val(x-1, y) == Cell::Empty { heur += value; }
+						if x<field.get_w()-1 && field.get_val(x+1, y) == Cell::Empty { heur += value; }
 						if y<field.get_h()-1
 						{
-							if x>=1 && field.get_val(x-1, y+1) == 0 { heur += value; }
-							if x<field.get_w()-1 && field.get_val(x+1, y+1) == 0 { heur += value; }
+							if x>=1 && field.get_val(x-1, y+1) == Cell::Empty { heur += value; }
+							if x<field.get_w()-1 && field.get_val(x+1, y+1) == Cell::Empty { heur += value; }
 						}
 					}
-					else if val == op
+					else if val == Cell::Stone(op)
 					{
-						if field.get_val(x+1, y) == op { if field.get_val(x+2, y) == op { pr3 -= 1; } else { pr2 -= 1; } }
-						if field.get_val(x+1, y+1) == op { if field.get_val(x+2, y+2) == op { pr3 -= 1; } else { pr2 -= 1; } }
-						if field.get_val(x, y+1) == op { if field.get_val(x, y+2) == op { pr3 -= 1; } else { pr2 -= 1; } }
-						if x>=1 && field.get_val(x-1, y+1) == op { if x>=2 && field.get_val(x-2, y+2) == op { pr3 -= 1; } else { pr2 -= 1; } }
+						for &(dx, dy) in DIRECTIONS.iter()
+						{
+							let len = PlayerMinimax::row_len(field, x, y, dx, dy);
+							if len+1 >= win { pr3 -= 1; } else if len >= 2 && len+2 == win { pr2 -= 1; }
+						}
 						//free squares
 						if y>=1
 						{
-							if x>=1 && field.get_val(x-1, y-1) == 0 { heur -= value; }
-							if field.get_val(x, y-1) == 0 { heur -= value; }
-							if x<field.get_w()-1 && field.get_val(x+1, y-1) == 0 { heur -= value; }
+							if x>=1 && field.get_val(x-1, y-1) == Cell::Empty { heur -= value; }
+							if field.get_val(x, y-1) == Cell::Empty { heur -= value; }
+							if x<field.get_w()-1 && field.get_val(x+1, y-1) == Cell::Empty { heur -= value; }
 						}
-						if x>=1 && field.get_val(x-1, y) == 0 { heur -= value; }
-						if x<field.get_w()-1 && field.get_val(x+1, y) == 0 { heur -= value; }
+						if x>=1 && field.get_val(x-1, y) == Cell::Empty { heur -= value; }
+						if x<field.get_w()-1 && field.get_val(x+1, y) == Cell::Empty { heur -= value; }
 						if y<field.get_h()-1
 						{
-							if x>=1 && field.get_val(x-1, y+1) == 0 { heur -= value; }
-							if x<field.get_w()-1 && field.get_val(x+1, y+1) == 0 { heur -= value; }
+							if x>=1 && field.get_val(x-1, y+1) == Cell::Empty { heur -= value; }
+							if x<field.get_w()-1 && field.get_val(x+1, y+1) == Cell::Empty { heur -= value; }
 						}
 					}
 				}
 			}
-			heur += (2*pr2 + 5*pr3) as f64; //add up to final score (3 rows count far more than 2 rows)
+			heur += (2*pr2 + 5*pr3) as f64; //add up to final score (longer rows count far more than shorter rows)
 			return heur;
 		}
 	}
 	
-	fn minimax(field:&mut Field, p:i32, deep:u32) -> f64
+	/// evaluates the windows of win length squares from the view of p: windows, that can still become a win, count by the square of
+	/// their stones. a threat is an empty square completing a window, it is playable if the square below is filled. threats, that
+	/// can not be played yet, decide the endgame by zugzwang: the first player can usually use threats on odd rows (counted from the
+	/// bottom), the second player threats on even rows
+	fn threat_heur(field:&Field, p:PlayerId) -> f64
 	{
-		let op = if p == 1 {2} else {1};
-		if deep > DEEPNESS { return PlayerMinimax::heur(field, if deep%2 == 0 {op} else {p}, deep); } //leaf node -> return evaluated heuristic
+		let (w, h, win) = (field.get_w() as i32, field.get_h() as i32, field.get_win() as i32);
+		let players = [PlayerId::P1, PlayerId::P2];
+		let mut heur = 0.0;
+		let mut threats = [0u64; 2]; //threat squares of the players as bitboard
+		for y in 0..h
+		{
+			for x in 0..w
+			{
+				for &(dx, dy) in DIRECTIONS.iter()
+				{
+					let (ex, ey) = (x + dx*(win-1), y + dy*(win-1)); //end of the window
+					if ex < 0 || ex >= w || ey >= h { continue; }
+					let mut stones = [0; 2];
+					let mut empty = 0;
+					for i in 0..win
+					{
+						let (cx, cy) = (x + dx*i, y + dy*i);
+						match field.get_val(cx as u32, cy as u32)
+						{
+							Cell::Stone(q) => stones[q.index()] += 1,
+							Cell::Empty => empty = cy*w + cx,
+						}
+					}
+					for &q in players.iter()
+					{
+						let (own, other) = (stones[q.index()], stones[q.other().index()]);
+						if own == 0 || other > 0 { continue; } //window is empty or can not be won anymore
+						heur += if q == p { (own*own) as f64 } else { -(own*own) as f64 };
+						if own == win-1 { threats[q.index()] |= 1u64 << empty; }
+					}
+				}
+			}
+		}
+		
+		let to_move = field.get_player_to_move();
+		let first = field.get_first_player();
+		for &q in players.iter()
+		{
+			let mut value = 0.0;
+			let mut playable = 0;
+			let mut bits = threats[q.index()];
+			while bits != 0
+			{
+				let i = bits.trailing_zeros() as i32;
+				bits &= bits - 1;
+				let (x, y) = (i % w, i / w);
+				if y == h-1 || field.get_val(x as u32, (y+1) as u32) != Cell::Empty { playable += 1; continue; }
+				let odd = (h - y) % 2 == 1;
+				value += if odd == (q == first) { GOOD_THREAT } else { BAD_THREAT };
+			}
+			if playable > 0
+			{ //the player to move wins with a playable threat, two playable threats of the other player can not both be blocked
+				value += if q == to_move || playable >= 2 { WINNING_THREAT } else { PLAYABLE_THREAT };
+			}
+			heur += if q == p { value } else { -value };
+		}
+		heur
+	}
+	
+	/// plain minimax, searching every move (reference for alphabeta, which returns the same values, see the tests)
+	fn minimax(field:&mut Field, p:PlayerId, deep:u32, depth:u32, eval:Heuristic) -> f64
+	{
+		let op = p.other();
+		if deep > depth { return PlayerMinimax::heur(field, if deep%2 == 0 {op} else {p}, deep, eval); } //leaf node -> return evaluated heuristic
 		let state = field.get_state(); //return early on game end
-		if state == -1 { return 0.0; }
-		else if state == p { return if deep%2 == 0 {-10002.0 + deep as f64} else {10002.0 - deep as f64}; }
-		else if state == op { return if deep%2 == 0 {10002.0 - deep as f64} else {-10002.0 + deep as f64}; }
+		if state == GameState::Draw { return 0.0; }
+		else if state == GameState::Win(p) { return if deep%2 == 0 {-10002.0 + deep as f64} else {10002.0 - deep as f64}; }
+		else if state == GameState::Win(op) { return if deep%2 == 0 {10002.0 - deep as f64} else {-10002.0 + deep as f64}; }
 		
 		//else: game running -> go deeper
 		let mut heur = if deep%2 == 0 { f64::INFINITY } else { f64::NEG_INFINITY };
@@ -105,8 +339,8 @@ impl PlayerMinimax
 		{
 			if field.is_valid_play(i)
 			{
-				field.play(p, i);
-				let val = PlayerMinimax::minimax(field, op, deep+1);
+				let _ = field.play(p, i); //valid play, checked above
+				let val = PlayerMinimax::minimax(field, op, deep+1, depth, eval);
 				field.undo();
 				if (deep%2 == 0 && val < heur) || (deep%2 == 1 && val > heur)
 				{
@@ -116,70 +350,260 @@ impl PlayerMinimax
 		}
 		heur
 	}
+	
+	/// minimax with alpha-beta pruning, same value as minimax for every node searched with alpha < value < beta
+	/// (the values of the root moves are exact when searched with an infinite window), if the transposition table is disabled.
+	/// with the table, values of deeper searches of the same position can be used
+	/// if the search is stopped, it returns a meaningless value
+	fn alphabeta(field:&mut Field, p:PlayerId, deep:u32, mut alpha:f64, mut beta:f64, search:&mut Search) -> f64
+	{
+		let op = p.other();
+		let (depth, order, tt) = (search.depth, search.order, search.tt);
+		search.count.nodes += 1;
+		if deep > depth { return PlayerMinimax::heur(field, if deep%2 == 0 {op} else {p}, deep, search.eval); } //leaf node -> return evaluated heuristic
+		let state = field.get_state(); //return early on game end
+		if state == GameState::Draw { return 0.0; }
+		else if state == GameState::Win(p) { return if deep%2 == 0 {-10002.0 + deep as f64} else {10002.0 - deep as f64}; }
+		else if state == GameState::Win(op) { return if deep%2 == 0 {10002.0 - deep as f64} else {-10002.0 + deep as f64}; }
+		
+		if search.stop.is_stopped() { return 0.0; }
+		
+		//an immediate win is the best possible value of this node
+		let win_val = if deep%2 == 0 {-10002.0 + (deep+1) as f64} else {10002.0 - (deep+1) as f64};
+		if order.iter().any(|&x| PlayerMinimax::is_winning_move(field, p, x)) { return win_val; }
+		//look up earlier searches of this position
+		let remaining = depth + 1 - deep;
+		let mut tt_move = None;
+		if tt.is_enabled() { search.count.tt_probes += 1; }
+		if let Some(entry) = tt.probe(field)
+		{
+			search.count.tt_hits += 1;
+			tt_move = entry.best;
+			if entry.depth >= remaining
+			{
+				let val = value_from_tt(entry.value, deep);
+				match entry.bound
+				{
+					Bound::Exact => return val,
+					Bound::Lower => if val > alpha { alpha = val; },
+					Bound::Upper => if val < beta { beta = val; },
+				}
+				if alpha >= beta { return val; }
+			}
+		}
+		let (alpha_start, beta_start) = (alpha, beta);
+		
+		//if the opponent threatens to win and his answer is searched, every other move looses -> only the block has to be searched
+		let mut moves = order;
+		let block;
+		if deep < depth
+		{
+			if let Some(&x) = order.iter().find(|&&x| PlayerMinimax::is_winning_move(field, op, x))
+			{
+				block = [x];
+				moves = &block;
+			}
+		}
+		
+		//else: game running -> go deeper, best move of earlier searches first
+		let first = tt_move.filter(|x| moves.contains(x));
+		let mut heur = if deep%2 == 0 { f64::INFINITY } else { f64::NEG_INFINITY };
+		let mut best = None;
+		for i in first.into_iter().chain(moves.iter().cloned().filter(|&x| Some(x) != first))
+		{
+			if field.is_valid_play(i)
+			{
+				let _ = field.play(p, i); //valid play, checked above
+				let val = PlayerMinimax::alphabeta(field, op, deep+1, alpha, beta, search);
+				field.undo();
+				if deep%2 == 0
+				{ //opponent moves -> minimize
+					if val < heur { heur = val; best = Some(i); }
+					if heur < beta { beta = heur; }
+				}
+				else
+				{ //own move -> maximize
+					if val > heur { heur = val; best = Some(i); }
+					if heur > alpha { alpha = heur; }
+				}
+				if alpha >= beta { break; } //cut-off, the other player avoids this node
+			}
+		}
+		
+		if search.stop.is_stopped() { return heur; } //incomplete search, do not store
+		let bound = if heur <= alpha_start { Bound::Upper } else if heur >= beta_start { Bound::Lower } else { Bound::Exact };
+		tt.store(field, value_to_tt(heur, deep), remaining, bound, best);
+		heur
+	}
+	
+	/// searches the moves of the root in the given order, returns the best one (the first of equal ones) and the values of all moves
+	fn root(field:&mut Field, p:PlayerId, root_order:&[u32], search:&mut Search) -> Result<Choice, Error>
+	{
+		let op = p.other();
+		let mut best = None;
+		let mut alpha = f64::NEG_INFINITY;
+		let mut scores = Vec::new();
+		for &x in root_order.iter()
+		{
+			if field.play(p, x).is_ok()
+			{
+				let val = PlayerMinimax::alphabeta(field, op, 2, alpha, f64::INFINITY, search);
+				field.undo();
+				scores.push((x, val));
+				if best.is_none() || val > alpha
+				{
+					alpha = val;
+					best = Some(x);
+				}
+			}
+		}
+		best.map(|x| (x, scores)).ok_or(Error::GameOver) //no valid move
+	}
+	
+	/// searches all moves to the given depth and returns the best one with the values of all moves, None if the deadline was over before finishing.
+	/// the helper threads of the pool search the same position at the same time (Lazy SMP), in other move orders and partly deeper.
+	/// their results are not used directly, but they fill the shared transposition table with values the main search can use.
+	/// the searched nodes of all threads are added to count
+	fn search(&self, field:&mut Field, depth:u32, deadline:Option<Instant>, count:&mut Counters) -> Result<Option<Choice>, Error>
+	{
+		let p = self.pid;
+		let eval = self.config.heuristic;
+		let order = PlayerMinimax::move_order(field.get_w());
+		let stop = Stop { deadline, flag: Arc::new(AtomicBool::new(false)) };
+		
+		let (done_sender, done) = mpsc::channel::<Counters>();
+		if let Some(pool) = self.pool
+		{
+			for i in 1..(self.config.threads - 1).min(pool.size())+1
+			{
+				let mut hfield = field.clone();
+				let mut root_order = order.clone();
+				root_order.rotate_left(i % order.len());
+				let (order, tt, stop, done_sender) = (order.clone(), self.tt.clone(), stop.clone(), done_sender.clone());
+				pool.execute(move ||
+					{
+						let mut search = Search { depth: depth + (i % 2) as u32, order: &order, tt: &tt, stop: &stop, eval, count: Counters::default() };
+						let _ = PlayerMinimax::root(&mut hfield, p, &root_order, &mut search);
+						let _ = done_sender.send(search.count);
+					});
+			}
+		}
+		drop(done_sender);
+		
+		let mut search = Search { depth, order: &order, tt: &self.tt, stop: &stop, eval, count: Counters::default() };
+		let result = PlayerMinimax::root(field, p, &order, &mut search);
+		let finished = !stop.is_stopped();
+		stop.flag.store(true, Ordering::Relaxed); //stop the helpers
+		count.add(&search.count);
+		while let Ok(helper) = done.recv() //the channel closes when all helpers are finished
+		{
+			count.add(&helper);
+		}
+		
+		let (x, mut scores) = result?;
+		scores.sort_by_key(|&(x, _)| x);
+		Ok(if finished { Some((x, scores)) } else { None })
+	}
 }
 
 impl Player for PlayerMinimax
 {
 	#[allow(unused_variables)]
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
-		if DEEPNESS < 1 { return false; }
+		if self.config.depth < 1 { return Err(Error::InvalidConfig("search depth has to be at least 1".to_string())); }
+		if self.config.threads < 1 { return Err(Error::InvalidConfig("at least 1 thread is needed".to_string())); }
 		self.initialized = true;
 		self.pid = p;
-		true
+		self.startp = field.get_first_player();
+		self.tt = Arc::new(TransTable::new(self.config.tt_memory, true));
+		self.pool = if self.config.threads > 1 { Some(pool::shared()) } else { None };
+		Ok(())
 	}
 	
-	#[allow(unused_variables)]
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
-		//nothing
+		self.startp = p;
+		self.tt.clear(); //new game
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
-		
-		let p = self.pid;
-		let op = if p == 1 {2} else {1};
-		let mut handles = Vec::new();
-		//spawn threads (one for each choice)
-		for i in 0..field.get_w()
-		{
-			let mut pfield = field.clone();
-			handles.push(thread::spawn(move ||
-				{
-					if pfield.play(p, i) { PlayerMinimax::minimax(&mut pfield, op, 2) }
-					else { f64::NEG_INFINITY }
-					//undo not needed, because it was cloned and will be dropped
-				}));
-		}
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
 		
-		//decide which action to take
-		let mut x:u32 = 0;
-		let mut max = f64::NEG_INFINITY;
-		for i in 0..field.get_w()
-		{
-			let res = handles.pop().unwrap().join();
-			if res.is_err() { return false; }
-			let res = res.unwrap();
-			if max < res || !field.is_valid_play(x)
+		let mut field = field.clone(); //the search plays and takes back moves on it
+		let start = Instant::now();
+		let mut count = Counters::default();
+		let (x, scores, depth) = match context.time_for_move(&field, self.pid, self.time_limit)
 			{
-				max = res;
-				x = field.get_w()-i-1;
-			}
-		}
-		
-		//debug
-		//println!("Heur: {}", max);
+				None =>
+					{
+						let (x, scores) = self.search(&mut field, self.config.depth, None, &mut count)?.unwrap(); //can not time out
+						(x, scores, self.config.depth)
+					},
+				Some(limit) =>
+					{ //iterative deepening: use the result of the last search, that finished in time
+						let deadline = start + limit;
+						let max_depth = field.get_size() - field.get_turns().len() as u32; //searching deeper than the game lasts does not change anything
+						let (mut x, mut scores) = self.search(&mut field, 1, None, &mut count)?.unwrap(); //always finish the first search to have a move
+						let mut finished = 1;
+						for depth in 2..max_depth+1
+						{
+							match self.search(&mut field, depth, Some(deadline), &mut count)?
+							{
+								Some(result) => { x = result.0; scores = result.1; finished = depth; },
+								None => break,
+							}
+						}
+						(x, scores, finished)
+					},
+			};
+		let pv = principal_variation(&field, &self.tt, x, depth);
+		self.info = Some(count.to_info(scores, depth, start.elapsed(), pv));
 		
-		//play (actually should always be true, unless game was finished before)
-		field.play(self.pid, x)
+		Ok(x)
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		//nothing
+	}	
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		self.info.as_ref()
+	}
+}
+
+impl Analyze for PlayerMinimax
+{
+	/// values of a full window search of every move to the search depth (single threaded, the time limit is not used)
+	fn analyze(&mut self, field:&Field) -> Result<Vec<(u32, f64)>, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		
+		//the stored values are from the view of the player and depend on the starting player, but the keys are the stones only
+		let p = field.get_player_to_move();
+		let reset = p != self.pid || field.get_first_player() != self.startp;
+		if reset { self.tt.clear(); }
+		let mut field = field.clone();
+		let order = PlayerMinimax::move_order(field.get_w());
+		let stop = Stop { deadline: None, flag: Arc::new(AtomicBool::new(false)) };
+		let mut search = Search { depth: self.config.depth, order: &order, tt: &self.tt, stop: &stop, eval: self.config.heuristic, count: Counters::default() };
+		let mut scores = Vec::new();
+		for x in 0..field.get_w()
+		{
+			if field.play(p, x).is_ok()
+			{
+				let val = PlayerMinimax::alphabeta(&mut field, p.other(), 2, f64::NEG_INFINITY, f64::INFINITY, &mut search);
+				field.undo();
+				scores.push((x, val));
+			}
+		}
+		if reset { self.tt.clear(); }
+		Ok(scores)
 	}
 }
 
@@ -190,3 +614,134 @@ impl Drop for PlayerMinimax
 		//nothing to do
 	}
 }
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{PlayerMinimax, MinimaxConfig, Heuristic, Stop, Search};
+	use super::super::{Player, Analyze, Counters};
+	use super::super::transposition::TransTable;
+	use super::super::super::context::GameContext;
+	use super::super::super::error::Error;
+	use super::super::super::field::{Field, PlayerId};
+	use std::f64;
+	use std::sync::Arc;
+	use std::sync::atomic::AtomicBool;
+	
+	const POSITIONS:[&str; 8] = ["", "4", "4453", "44443", "334455", "12432434", "7665545", "443322"];
+	
+	#[test]
+	fn alphabeta_matches_minimax()
+	{
+		let stop = Stop { deadline: None, flag: Arc::new(AtomicBool::new(false)) };
+		let tt = TransTable::new(0, true);
+		for &eval in [Heuristic::Classic, Heuristic::Threats].iter()
+		{
+			for &moves in POSITIONS.iter()
+			{
+				let mut field = Field::from_moves(moves).unwrap();
+				let p = field.get_player_to_move();
+				let order = PlayerMinimax::move_order(field.get_w());
+				for depth in 2..5
+				{
+					let mut best = f64::NEG_INFINITY;
+					for x in 0..field.get_w()
+					{
+						if field.play(p, x).is_err() { continue; }
+						let expected = PlayerMinimax::minimax(&mut field, p.other(), 2, depth, eval);
+						let mut search = Search { depth, order: &order, tt: &tt, stop: &stop, eval, count: Counters::default() };
+						let val = PlayerMinimax::alphabeta(&mut field, p.other(), 2, f64::NEG_INFINITY, f64::INFINITY, &mut search);
+						field.undo();
+						assert_eq!(val, expected, "{:?}, moves {}, depth {}, column {}", eval, moves, depth, x+1);
+						best = best.max(expected);
+					}
+					
+					//the narrowed windows of the root search find the same best value
+					let mut search = Search { depth, order: &order, tt: &tt, stop: &stop, eval, count: Counters::default() };
+					let (x, scores) = PlayerMinimax::root(&mut field, p, &order, &mut search).unwrap();
+					assert_eq!(scores.iter().find(|&&(y, _)| y == x).unwrap().1, best, "{:?}, moves {}, depth {}", eval, moves, depth);
+				}
+			}
+		}
+	}
+	
+	fn new_player(field:&Field, eval:Heuristic) -> Box<PlayerMinimax>
+	{
+		let mut player = PlayerMinimax::with_config(MinimaxConfig { depth: 4, heuristic: eval, threads: 1, ..MinimaxConfig::default() });
+		player.init(field, field.get_player_to_move(), &GameContext::new(field)).unwrap();
+		player
+	}
+	
+	#[test]
+	fn analyze_agrees_with_play()
+	{
+		for &eval in [Heuristic::Classic, Heuristic::Threats].iter()
+		{
+			for &moves in POSITIONS.iter()
+			{
+				let field = Field::from_moves(moves).unwrap();
+				let mut player = new_player(&field, eval);
+				let x = player.play(&field, &GameContext::new(&field)).unwrap();
+				let scores = player.analyze(&field).unwrap();
+				let best = scores.iter().map(|&(_, val)| val).fold(f64::NEG_INFINITY, f64::max);
+				assert_eq!(scores.iter().find(|&&(y, _)| y == x).unwrap().1, best, "{:?}, moves {}", eval, moves);
+				assert_eq!(player.search_info().unwrap().score(), Some(best));
+			}
+		}
+	}
+	
+	#[test]
+	fn analyze_does_not_use_values_of_other_games()
+	{
+		//positions of games the other player started, with the same or the other player to move. the searches reach the same stones
+		let games = [(PlayerId::P1, "12", PlayerId::P2, "213"), (PlayerId::P2, "43", PlayerId::P1, "345"), (PlayerId::P1, "4433", PlayerId::P2, "3344")];
+		for &eval in [Heuristic::Classic, Heuristic::Threats].iter()
+		{
+			for &(first, moves, other_first, other_moves) in games.iter()
+			{
+				let mut field = Field::new(7, 6, 4);
+				field.play_moves_from(first, moves).unwrap();
+				let mut other = Field::new(7, 6, 4);
+				other.play_moves_from(other_first, other_moves).unwrap();
+				
+				let mut player = new_player(&field, eval);
+				player.startp(first);
+				player.play(&field, &GameContext::new(&field)).unwrap(); //fills the table
+				let scores = player.analyze(&other).unwrap();
+				assert_eq!(scores, PlayerMinimax::analyze(&mut new_player(&other, eval), &other).unwrap(), "{:?}, moves {}", eval, other_moves);
+				assert_eq!(player.analyze(&field).unwrap(), PlayerMinimax::analyze(&mut new_player(&field, eval), &field).unwrap()); //and back
+			}
+		}
+	}
+	
+	#[test]
+	fn helper_threads_share_one_pool()
+	{
+		let field = Field::from_moves("4453").unwrap();
+		let mut players = Vec::new();
+		for _ in 0..2
+		{
+			let mut player = PlayerMinimax::with_config(MinimaxConfig { depth: 4, threads: 3, ..MinimaxConfig::default() });
+			player.init(&field, field.get_player_to_move(), &GameContext::new(&field)).unwrap();
+			let x = player.play(&field, &GameContext::new(&field)).unwrap();
+			assert!(field.is_valid_play(x));
+			players.push(player);
+		}
+		assert!(::std::ptr::eq(players[0].pool.unwrap(), players[1].pool.unwrap()));
+		assert!(new_player(&field, Heuristic::Classic).pool.is_none());
+	}
+	
+	#[test]
+	fn no_search_after_the_game_ended()
+	{
+		let field = Field::from_moves("1212121").unwrap();
+		let mut player = new_player(&Field::new(7, 6, 4), Heuristic::Classic);
+		match player.play(&field, &GameContext::new(&field))
+		{
+			Err(Error::GameOver) => (),
+			result => panic!("{:?}", result),
+		}
+	}
+}
+
@@ -6,14 +6,162 @@ pub mod ai_q_player;
 pub mod minimax_player;
 pub mod ai_value_player;
 pub mod ai_qoff_player;
+pub mod model;
+pub mod transposition;
+pub mod pool;
+pub mod solver_player;
+pub mod mcts_player;
+pub mod alpha_zero_player;
 
-use super::field::Field;
+pub use self::io_player::PlayerIO;
+pub use self::random_player::PlayerRandom;
+pub use self::ai_q_player::PlayerAIQ;
+pub use self::minimax_player::PlayerMinimax;
+pub use self::ai_value_player::PlayerAIValue;
+pub use self::ai_qoff_player::PlayerAIQOff;
+pub use self::solver_player::PlayerSolver;
+pub use self::mcts_player::PlayerMCTS;
+pub use self::alpha_zero_player::PlayerAlphaZero;
+
+use super::field::{Field, PlayerId, GameState};
+use super::context::GameContext;
+use super::error::Error;
+use self::transposition::TransTable;
+use std::fmt;
+use std::time::{Duration, Instant};
 
 
 pub trait Player:Drop
 {
-	fn init(&mut self, field:&Field, p_id:i32) -> bool;
-	fn startp(&mut self, p_id:i32);
-	fn play(&mut self, field:&mut Field) -> bool;
-	fn outcome(&mut self, field:&mut Field, state:i32);
+	fn init(&mut self, field:&Field, p_id:PlayerId, context:&GameContext) -> Result<(), Error>;
+	fn startp(&mut self, p_id:PlayerId);
+	/// chooses the column (starting at 0) to play for its side, the game checks the move and plays it.
+	/// the context tells the history, clocks and match of the game
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>;
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext);
+	
+	/// learns from the games not learned from yet and writes the model of a learning player to its file, it is also written when the player is dropped. nothing to do for other players
+	fn save(&mut self) -> Result<(), Error>
+	{
+		Ok(())
+	}
+	
+	/// writes the model of a learning player to the given file, the player keeps using its own file. nothing to do for other players
+	#[allow(unused_variables)]
+	fn checkpoint(&self, path:&str) -> Result<(), Error>
+	{
+		Ok(())
+	}
+	
+	/// what the search of the last move found, None for players that do not search
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		None
+	}
+}
+
+/// players, that can score every column of a position without playing a move
+pub trait Analyze
+{
+	/// scores of all legal columns from the view of the player to move, higher is better (the scale depends on the player).
+	/// the player has to be initialized, the field is not changed
+	fn analyze(&mut self, field:&Field) -> Result<Vec<(u32, f64)>, Error>;
+}
+
+/// statistics and result of the search for a move. columns start at 0
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchInfo
+{
+	pub scores: Vec<(u32, f64)>, //value of every searched root column from the view of the player (with pruning, worse columns only have upper bounds)
+	pub depth: u32, //depth of the last finished search (length of the main line for tree searches)
+	pub nodes: u64, //searched positions of all threads and iterations (simulations for tree searches)
+	pub time: Duration,
+	pub pv: Vec<u32>, //principal variation: the expected moves of both players, starting with the chosen one
+	pub tt_probes: u64,
+	pub tt_hits: u64,
+}
+
+impl SearchInfo
+{
+	/// value of the chosen move
+	pub fn score(&self) -> Option<f64>
+	{
+		let x = self.pv.first()?;
+		self.scores.iter().find(|&&(column, _)| column == *x).map(|&(_, score)| score)
+	}
+	
+	pub fn nodes_per_sec(&self) -> f64
+	{
+		let secs = self.time.as_secs() as f64 + self.time.subsec_nanos() as f64 * 1e-9;
+		if secs > 0.0 { self.nodes as f64 / secs } else { 0.0 }
+	}
+	
+	/// share of transposition table lookups, that found the position, None without table
+	pub fn tt_hit_rate(&self) -> Option<f64>
+	{
+		if self.tt_probes == 0 { None } else { Some(self.tt_hits as f64 / self.tt_probes as f64) }
+	}
+}
+
+impl fmt::Display for SearchInfo
+{
+	/// one line like "depth 8, score 12.00, nodes 152340 (761700/s), tt hits 31.5%, pv 4 4 3, scores 1:-5.00 2:3.00 ..", columns start at 1
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "depth {}", self.depth)?;
+		if let Some(score) = self.score() { write!(f, ", score {:.2}", score)?; }
+		write!(f, ", nodes {} ({:.0}/s)", self.nodes, self.nodes_per_sec())?;
+		if let Some(rate) = self.tt_hit_rate() { write!(f, ", tt hits {:.1}%", rate * 100.0)?; }
+		let pv:Vec<String> = self.pv.iter().map(|x| (x+1).to_string()).collect();
+		write!(f, ", pv {}", pv.join(" "))?;
+		let scores:Vec<String> = self.scores.iter().map(|&(x, score)| format!("{}:{:.2}", x+1, score)).collect();
+		write!(f, ", scores {}", scores.join(" "))
+	}
+}
+
+/// counters of a searching thread, added up for the search info
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters
+{
+	nodes: u64,
+	tt_probes: u64,
+	tt_hits: u64,
+}
+
+impl Counters
+{
+	fn add(&mut self, other:&Counters)
+	{
+		self.nodes += other.nodes;
+		self.tt_probes += other.tt_probes;
+		self.tt_hits += other.tt_hits;
+	}
+	
+	/// search info with these counters
+	fn to_info(self, scores:Vec<(u32, f64)>, depth:u32, time:Duration, pv:Vec<u32>) -> SearchInfo
+	{
+		SearchInfo { scores, depth, nodes: self.nodes, time, pv, tt_probes: self.tt_probes, tt_hits: self.tt_hits }
+	}
+}
+
+/// principal variation after searching the position to depth and choosing x: the best moves stored in the transposition table
+fn principal_variation(field:&Field, tt:&TransTable, x:u32, depth:u32) -> Vec<u32>
+{
+	let mut field = field.clone();
+	let mut pv = Vec::new();
+	let mut next = Some(x);
+	while let Some(x) = next
+	{
+		let p = field.get_player_to_move();
+		if pv.len() as u32 >= depth || field.play(p, x).is_err() { break; }
+		pv.push(x);
+		next = if field.get_state().is_over() { None } else { tt.probe(&field).and_then(|entry| entry.best) };
+	}
+	pv
+}
+
+/// checks if the time of a search with the given deadline is over
+fn is_timed_out(deadline:Option<Instant>) -> bool
+{
+	deadline.is_some_and(|deadline| Instant::now() >= deadline)
 }
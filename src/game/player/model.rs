@@ -0,0 +1,74 @@
+//! model files of the learning players: games played, neural net and additional data (as JSON)
+#![allow(dead_code)]
+
+extern crate nn;
+extern crate rustc_serialize;
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::io::prelude::*;
+use std::path::Path;
+use self::rustc_serialize::{json, Decodable};
+use self::nn::NN;
+use super::super::error::Error;
+
+
+pub struct Model
+{
+	pub games_played: u32,
+	pub nn: NN,
+	pub data: String, //remaining file content, e.g. the JSON encoded experience buffer
+}
+
+/// file of a model: the chosen file if not empty, else the default name (like AIValue-7x6.NN) in the models directory
+/// (empty for the current directory)
+pub fn path(model:&str, dir:&str, default:&str) -> String
+{
+	if !model.is_empty() { model.to_string() }
+	else { Path::new(dir).join(default).to_string_lossy().into_owned() }
+}
+
+/// loads a model file, Ok(None) if it does not exist yet
+pub fn load(filename:&str) -> Result<Option<Model>, Error>
+{
+	let file = match File::open(filename)
+		{
+			Ok(file) => file,
+			Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(Error::Io(err)),
+		};
+	let mut reader = BufReader::new(file);
+	let mut datas = String::new();
+	let mut nns = String::new();
+	let mut data = String::new();
+	
+	reader.read_line(&mut datas)?;
+	reader.read_line(&mut nns)?;
+	reader.read_to_string(&mut data)?;
+	
+	let games_played = datas.trim().parse::<u32>()
+		.map_err(|err| Error::CorruptModel(filename.to_string(), format!("games played: {}", err)))?;
+	let nn = json::decode::<NN>(nns.trim())
+		.map_err(|err| Error::CorruptModel(filename.to_string(), format!("neural net: {}", err)))?;
+	Ok(Some(Model { games_played, nn, data }))
+}
+
+/// decodes the additional data of a model
+pub fn decode_data<T:Decodable>(filename:&str, data:&str) -> Result<T, Error>
+{
+	json::decode(data).map_err(|err| Error::CorruptModel(filename.to_string(), format!("data: {}", err)))
+}
+
+/// writes a model file (games played, neural net, additional data in this order), missing directories are created
+pub fn save(filename:&str, games_played:u32, nn:&NN, data:&str) -> Result<(), Error>
+{
+	if let Some(dir) = Path::new(filename).parent() { fs::create_dir_all(dir)?; }
+	let file = File::create(filename)?;
+	let mut writer = BufWriter::new(file);
+	
+	writeln!(&mut writer, "{}", games_played)?;
+	writeln!(&mut writer, "{}", nn.to_json())?;
+	write!(&mut writer, "{}", data)?;
+	writer.flush()?;
+	Ok(())
+}
@@ -0,0 +1,82 @@
+//! persistent worker threads, so that searches do not start new threads for every move
+#![allow(dead_code)]
+
+extern crate num_cpus;
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+
+/// number of threads to use by default, one for every CPU
+pub fn default_threads() -> usize
+{
+	num_cpus::get()
+}
+
+/// pool shared by the searches of all players of the process, created on first use with a worker for every CPU besides the searching one
+pub fn shared() -> &'static ThreadPool
+{
+	static POOL:OnceLock<ThreadPool> = OnceLock::new();
+	POOL.get_or_init(|| ThreadPool::new(default_threads().max(2) - 1))
+}
+
+pub struct ThreadPool
+{
+	sender: Option<Sender<Job>>, //dropped first to stop the workers
+	workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool
+{
+	/// pool with the given number of worker threads
+	pub fn new(threads:usize) -> ThreadPool
+	{
+		let (sender, receiver) = mpsc::channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		let mut workers = Vec::with_capacity(threads);
+		for _ in 0..threads
+		{
+			let receiver = receiver.clone();
+			workers.push(thread::spawn(move ||
+				{
+					loop
+					{
+						let job = receiver.lock().unwrap().recv(); //lock is released before the job runs
+						match job
+						{
+							Ok(job) => job(),
+							Err(_) => break, //pool was dropped
+						}
+					}
+				}));
+		}
+		ThreadPool { sender: Some(sender), workers }
+	}
+	
+	pub fn size(&self) -> usize
+	{
+		self.workers.len()
+	}
+	
+	/// runs the job on the next free worker
+	pub fn execute<F>(&self, job:F)
+		where F: FnOnce() + Send + 'static
+	{
+		self.sender.as_ref().unwrap().send(Box::new(job)).expect("Thread pool workers stopped!");
+	}
+}
+
+impl Drop for ThreadPool
+{
+	fn drop(&mut self)
+	{
+		self.sender = None;
+		for worker in self.workers.drain(..)
+		{
+			let _ = worker.join();
+		}
+	}
+}
@@ -5,13 +5,15 @@ extern crate rand;
 
 use self::rand::{Rng, ThreadRng};
 use super::Player;
-use super::super::field::Field;
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState};
 
 
 pub struct PlayerRandom
 {
 	initialized: bool,
-	pid: i32, //player ID
+	pid: PlayerId, //player ID
 	rng: Box<ThreadRng>,
 }
 
@@ -19,42 +21,38 @@ impl PlayerRandom
 {
 	pub fn new() -> Box<PlayerRandom>
 	{
-		Box::new(PlayerRandom { initialized: false, pid: 0, rng: Box::new(rand::thread_rng()) })
+		Box::new(PlayerRandom { initialized: false, pid: PlayerId::P1, rng: Box::new(rand::thread_rng()) })
 	}
 }
 
 impl Player for PlayerRandom
 {
 	#[allow(unused_variables)]
-	fn init(&mut self, field:&Field, p:i32) -> bool
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
 	{
 		self.initialized = true;
 		self.pid = p;
-		true
+		Ok(())
 	}
 	
 	#[allow(unused_variables)]
-	fn startp(&mut self, p:i32)
+	fn startp(&mut self, p:PlayerId)
 	{
 		//nothing
 	}
 	
-	fn play(&mut self, field:&mut Field) -> bool
+	#[allow(unused_variables)]
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
 	{
-		if !self.initialized { return false; }
-		
-		let w = field.get_w();
-		let mut random = (*self.rng).gen::<u32>() % w;
-		while !field.play(self.pid, random)
-		{
-			random = (*self.rng).gen::<u32>() % w;
-		}
+		if !self.initialized { return Err(Error::NotInitialized); }
 		
-		true
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		let valid:Vec<u32> = (0..field.get_w()).filter(|&x| field.is_valid_play(x)).collect();
+		(*self.rng).choose(&valid).cloned().ok_or(Error::GameOver) //uniformly among the columns, that are not full
 	}
 	
 	#[allow(unused_variables)]
-	fn outcome(&mut self, field:&mut Field, state:i32)
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
 	{
 		//nothing
 	}
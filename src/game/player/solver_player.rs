@@ -0,0 +1,131 @@
+//! Player playing perfectly using the solver (moves early in the game can take very long on the standard board)
+#![allow(dead_code)]
+
+use super::{Player, Analyze, SearchInfo, Counters};
+use super::super::config::{parse_value, unknown_setting};
+use super::super::context::GameContext;
+use super::super::error::Error;
+use super::super::field::{Field, PlayerId, GameState};
+use super::super::solver::{Solver, SOLVER_TT_MEMORY};
+use std::time::Instant;
+
+
+/// settings of the solver player
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolverConfig
+{
+	pub tt_memory: usize, //bytes of the solver's transposition table
+}
+
+impl Default for SolverConfig
+{
+	fn default() -> SolverConfig
+	{
+		SolverConfig { tt_memory: SOLVER_TT_MEMORY }
+	}
+}
+
+impl SolverConfig
+{
+	/// changes the setting key to the given value
+	pub fn set(&mut self, key:&str, value:&str) -> Result<(), Error>
+	{
+		match key
+		{
+			"tt_memory" => self.tt_memory = parse_value(key, value)?,
+			_ => return Err(unknown_setting(key)),
+		}
+		Ok(())
+	}
+}
+
+pub struct PlayerSolver
+{
+	initialized: bool,
+	pid: PlayerId, //player ID
+	config: SolverConfig,
+	solver: Option<Solver>, //kept between games, the solved positions stay valid
+	info: Option<SearchInfo>, //search of the last move
+}
+
+impl PlayerSolver
+{
+	pub fn new() -> Box<PlayerSolver>
+	{
+		PlayerSolver::with_config(SolverConfig::default())
+	}
+	
+	pub fn with_config(config:SolverConfig) -> Box<PlayerSolver>
+	{
+		Box::new(PlayerSolver { initialized: false, pid: PlayerId::P1, config, solver: None, info: None })
+	}
+	
+	/// sets the memory of the transposition table in bytes, has to be called before init
+	pub fn set_tt_memory(&mut self, memory:usize)
+	{
+		self.config.tt_memory = memory;
+	}
+}
+
+impl Player for PlayerSolver
+{
+	#[allow(unused_variables)]
+	fn init(&mut self, field:&Field, p:PlayerId, context:&GameContext) -> Result<(), Error>
+	{
+		self.solver = Some(Solver::with_memory(field, self.config.tt_memory)?);
+		self.initialized = true;
+		self.pid = p;
+		Ok(())
+	}
+	
+	#[allow(unused_variables)]
+	fn startp(&mut self, p:PlayerId)
+	{
+		//nothing
+	}
+	
+	#[allow(unused_variables)]
+	fn play(&mut self, field:&Field, context:&GameContext) -> Result<u32, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		
+		let start = Instant::now();
+		let solver = self.solver.as_mut().unwrap();
+		let nodes = solver.get_nodes();
+		let (x, score) = solver.best_move(field)?;
+		let count = Counters { nodes: solver.get_nodes() - nodes, ..Counters::default() };
+		let depth = field.get_size() - field.get_turns().len() as u32; //always searches to the end of the game
+		self.info = Some(count.to_info(vec![(x, score as f64)], depth, start.elapsed(), vec![x]));
+		Ok(x)
+	}
+	
+	#[allow(unused_variables)]
+	fn outcome(&mut self, field:&Field, state:GameState, context:&GameContext)
+	{
+		//nothing
+	}
+	
+	fn search_info(&self) -> Option<&SearchInfo>
+	{
+		self.info.as_ref()
+	}
+}
+
+impl Analyze for PlayerSolver
+{
+	/// exact scores of every move: positive wins, the higher the sooner (see Solver::describe), 0 draws, negative loses
+	fn analyze(&mut self, field:&Field) -> Result<Vec<(u32, f64)>, Error>
+	{
+		if !self.initialized { return Err(Error::NotInitialized); }
+		let scores = self.solver.as_mut().unwrap().analyze(field)?;
+		Ok(scores.iter().enumerate().filter_map(|(x, score)| score.map(|score| (x as u32, score as f64))).collect())
+	}
+}
+
+impl Drop for PlayerSolver
+{
+	fn drop(&mut self)
+	{
+		//nothing to do
+	}
+}
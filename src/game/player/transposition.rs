@@ -0,0 +1,255 @@
+//! transposition table for the minimax searches, can be shared by searching threads
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use super::super::field::Field;
+
+pub const TT_MEMORY:usize = 16 * 1024 * 1024; //default memory of a table in bytes
+const WIN_BOUND:f64 = 9000.0; //values beyond this are won or lost games, their distance to the game end is stored
+const NO_MOVE:u64 = 0xFF;
+
+
+/// what the stored value says about the real value of the position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound
+{
+	Exact,
+	Lower, //search failed high, real value is at least the stored one
+	Upper, //search failed low, real value is at most the stored one
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry
+{
+	pub value: f64, //from the view of the searching player
+	pub depth: u32, //remaining search depth the value was computed with
+	pub bound: Bound,
+	pub best: Option<u32>, //best column found
+}
+
+/// entries are written without locks, check is the key xor the data, so torn writes are detected as misses
+struct Slot
+{
+	check: AtomicU64,
+	value: AtomicU64,
+	meta: AtomicU64, //depth, bound and best move
+}
+
+pub struct TransTable
+{
+	slots: Vec<Slot>,
+	mirror: bool, //mirrored positions share the entries
+}
+
+impl TransTable
+{
+	/// table using about the given number of bytes, 0 disables it.
+	/// mirrored positions share their entries, if mirror is set (only for evaluations, that are symmetric)
+	pub fn new(memory:usize, mirror:bool) -> TransTable
+	{
+		let num = memory / ::std::mem::size_of::<Slot>();
+		let mut slots = Vec::with_capacity(num);
+		for _ in 0..num
+		{
+			slots.push(Slot { check: AtomicU64::new(0), value: AtomicU64::new(0), meta: AtomicU64::new(0) });
+		}
+		TransTable { slots, mirror }
+	}
+	
+	pub fn is_enabled(&self) -> bool
+	{
+		!self.slots.is_empty()
+	}
+	
+	pub fn clear(&self)
+	{
+		for slot in self.slots.iter()
+		{
+			slot.check.store(0, Ordering::Relaxed);
+			slot.value.store(0, Ordering::Relaxed);
+			slot.meta.store(0, Ordering::Relaxed);
+		}
+	}
+	
+	/// position key, mirrored positions share the key if enabled. also returns if the position is the mirrored one
+	fn key(&self, field:&Field) -> (u64, bool)
+	{
+		let hash = field.get_hash();
+		if !self.mirror { return (hash, false); }
+		let mirror = field.get_mirror_hash();
+		if mirror < hash { (mirror, true) } else { (hash, false) }
+	}
+	
+	fn mirror_move(field:&Field, x:u32, mirrored:bool) -> u32
+	{
+		if mirrored { field.get_w()-1-x } else { x }
+	}
+	
+	pub fn probe(&self, field:&Field) -> Option<Entry>
+	{
+		if self.slots.is_empty() { return None; }
+		let (key, mirrored) = self.key(field);
+		let slot = &self.slots[(key % self.slots.len() as u64) as usize];
+		let value = slot.value.load(Ordering::Relaxed);
+		let meta = slot.meta.load(Ordering::Relaxed);
+		if meta == 0 || slot.check.load(Ordering::Relaxed) != key ^ value ^ meta { return None; }
+		
+		let best = (meta >> 16) & 0xFF;
+		Some(Entry {
+				value: f64::from_bits(value),
+				depth: (meta & 0xFF) as u32,
+				bound: match (meta >> 8) & 0xFF { 1 => Bound::Exact, 2 => Bound::Lower, _ => Bound::Upper },
+				best: if best == NO_MOVE { None } else { Some(TransTable::mirror_move(field, best as u32, mirrored)) },
+			})
+	}
+	
+	/// stores the search result of the position (always replaces)
+	pub fn store(&self, field:&Field, value:f64, depth:u32, bound:Bound, best:Option<u32>)
+	{
+		if self.slots.is_empty() { return; }
+		let (key, mirrored) = self.key(field);
+		let slot = &self.slots[(key % self.slots.len() as u64) as usize];
+		let bound = match bound { Bound::Exact => 1, Bound::Lower => 2, Bound::Upper => 3 };
+		let best = best.map_or(NO_MOVE, |x| TransTable::mirror_move(field, x, mirrored) as u64);
+		let meta = (depth.min(0xFF) as u64) | (bound << 8) | (best << 16);
+		let value = value.to_bits();
+		slot.check.store(key ^ value ^ meta, Ordering::Relaxed);
+		slot.value.store(value, Ordering::Relaxed);
+		slot.meta.store(meta, Ordering::Relaxed);
+	}
+}
+
+/// makes a value independent of the depth deep it was found in: wins are stored as distance to the game end
+pub fn value_to_tt(value:f64, deep:u32) -> f64
+{
+	if value > WIN_BOUND { value + deep as f64 }
+	else if value < -WIN_BOUND { value - deep as f64 }
+	else { value }
+}
+
+/// reverts value_to_tt for the depth deep the value is used in
+pub fn value_from_tt(value:f64, deep:u32) -> f64
+{
+	if value > WIN_BOUND { value - deep as f64 }
+	else if value < -WIN_BOUND { value + deep as f64 }
+	else { value }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{TransTable, Bound, value_to_tt, value_from_tt};
+	use super::super::super::field::{Field, PlayerId};
+	
+	fn field(moves:&str) -> Field
+	{
+		let mut field = Field::new(7, 6, 4);
+		field.play_moves(moves).unwrap();
+		field
+	}
+	
+	#[test]
+	fn store_and_probe()
+	{
+		let tt = TransTable::new(1024 * 1024, true);
+		assert!(tt.is_enabled());
+		let pos = field("4453");
+		assert!(tt.probe(&pos).is_none());
+		
+		tt.store(&pos, 12.5, 7, Bound::Lower, Some(2));
+		let entry = tt.probe(&pos).unwrap();
+		assert_eq!(entry.value, 12.5);
+		assert_eq!(entry.depth, 7);
+		assert_eq!(entry.bound, Bound::Lower);
+		assert_eq!(entry.best, Some(2));
+		
+		//entries are replaced, positions without a best move keep none
+		tt.store(&pos, -3.0, 2, Bound::Upper, None);
+		let entry = tt.probe(&pos).unwrap();
+		assert_eq!((entry.value, entry.depth, entry.bound, entry.best), (-3.0, 2, Bound::Upper, None));
+		
+		//other positions miss
+		assert!(tt.probe(&field("4454")).is_none());
+		
+		tt.clear();
+		assert!(tt.probe(&pos).is_none());
+	}
+	
+	#[test]
+	fn disabled_table_stores_nothing()
+	{
+		let tt = TransTable::new(0, true);
+		assert!(!tt.is_enabled());
+		let pos = field("4");
+		tt.store(&pos, 1.0, 3, Bound::Exact, Some(3));
+		assert!(tt.probe(&pos).is_none());
+	}
+	
+	#[test]
+	fn mirrored_positions_share_entries()
+	{
+		let tt = TransTable::new(1024 * 1024, true);
+		for &(moves, mirrored) in [("1123", "7765"), ("7765", "1123"), ("3345", "5543")].iter()
+		{
+			tt.clear();
+			let (pos, mirror) = (field(moves), field(mirrored));
+			assert_eq!(pos.get_hash(), mirror.get_mirror_hash());
+			tt.store(&pos, 5.0, 4, Bound::Exact, Some(1));
+			let entry = tt.probe(&mirror).unwrap();
+			assert_eq!(entry.value, 5.0);
+			assert_eq!(entry.best, Some(5)); //the best move is mirrored too
+			assert_eq!(tt.probe(&pos).unwrap().best, Some(1));
+		}
+		
+		//mirrored stones of the other player are a different position
+		let mut pos = Field::new(7, 6, 4);
+		pos.play_moves_from(PlayerId::P2, "1").unwrap();
+		tt.clear();
+		tt.store(&field("7"), 1.0, 1, Bound::Exact, None);
+		assert!(tt.probe(&pos).is_none());
+	}
+	
+	#[test]
+	fn mirror_keys_are_optional()
+	{
+		let tt = TransTable::new(1024 * 1024, false);
+		tt.store(&field("1123"), 5.0, 4, Bound::Exact, Some(1));
+		assert!(tt.probe(&field("7765")).is_none());
+		assert_eq!(tt.probe(&field("1123")).unwrap().best, Some(1));
+		
+		//symmetric positions are found either way
+		tt.store(&field("44"), 2.0, 3, Bound::Exact, Some(3));
+		assert_eq!(tt.probe(&field("44")).unwrap().best, Some(3));
+	}
+	
+	#[test]
+	fn win_values_are_stored_as_distance_to_the_game_end()
+	{
+		//a win at depth 9 found by a node at depth 3 is 6 plies away, also when used at depth 5
+		let win = 10002.0 - 9.0;
+		let stored = value_to_tt(win, 3);
+		assert_eq!(value_from_tt(stored, 3), win);
+		assert_eq!(value_from_tt(stored, 5), 10002.0 - 11.0);
+		assert_eq!(value_from_tt(stored, 0), 10002.0 - 6.0);
+		
+		let loss = -10002.0 + 9.0;
+		let stored = value_to_tt(loss, 3);
+		assert_eq!(value_from_tt(stored, 3), loss);
+		assert_eq!(value_from_tt(stored, 5), -10002.0 + 11.0);
+		assert_eq!(value_from_tt(stored, 0), -10002.0 + 6.0);
+		
+		//other values do not depend on the depth
+		for &value in [0.0, 42.0, -42.0, 8999.0, -8999.0].iter()
+		{
+			assert_eq!(value_to_tt(value, 4), value);
+			assert_eq!(value_from_tt(value, 4), value);
+		}
+		
+		//and survive the table
+		let tt = TransTable::new(1024 * 1024, true);
+		let pos = field("443");
+		tt.store(&pos, value_to_tt(win, 3), 6, Bound::Exact, Some(3));
+		assert_eq!(value_from_tt(tt.probe(&pos).unwrap().value, 5), 10002.0 - 11.0);
+	}
+}
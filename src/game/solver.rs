@@ -0,0 +1,581 @@
+//! perfect play solver: negamax on bitboards with alpha-beta, transposition table and null window searches
+#![allow(dead_code)]
+
+use super::error::Error;
+use super::field::{Field, Cell};
+
+pub const SOLVER_TT_MEMORY:usize = 64 * 1024 * 1024; //default memory of the solver's table in bytes
+const MAX_COLUMNS:usize = 21; //boards with at least 2 rows and a guard row fit at most 21 columns into 64 bits
+
+
+/// position from the view of the player to move, column-major bitboards with an empty guard row on top of every column
+#[derive(Clone, Copy)]
+struct Position
+{
+	current: u64, //stones of the player to move
+	mask: u64, //all stones
+	moves: u32, //number of stones played
+}
+
+/// board constants of a field size
+struct Board
+{
+	w: u32,
+	h: u32,
+	win: u32,
+	size: u32, //number of squares
+	bottom: u64, //lowest square of every column
+	board: u64, //all squares
+	shifts: [u32; 4], //bit distance of neighbours in the 4 directions
+}
+
+impl Board
+{
+	fn new(w:u32, h:u32, win:u32) -> Board
+	{
+		let mut bottom = 0u64;
+		for x in 0..w
+		{
+			bottom |= 1u64 << (x*(h+1));
+		}
+		let board = bottom * ((1u64 << h) - 1);
+		Board { w, h, win, size: w*h, bottom, board, shifts: [1, h+1, h, h+2] }
+	}
+	
+	fn top_mask(&self, x:u32) -> u64
+	{
+		1u64 << (self.h - 1 + x*(self.h+1))
+	}
+	
+	fn column_mask(&self, x:u32) -> u64
+	{
+		((1u64 << self.h) - 1) << (x*(self.h+1))
+	}
+	
+	fn can_play(&self, pos:&Position, x:u32) -> bool
+	{
+		pos.mask & self.top_mask(x) == 0
+	}
+	
+	/// lowest free square of every column
+	fn possible(&self, pos:&Position) -> u64
+	{
+		pos.mask.wrapping_add(self.bottom) & self.board
+	}
+	
+	/// plays a stone at the square move_bit, switching the player to move
+	fn play(&self, pos:&Position, move_bit:u64) -> Position
+	{
+		Position { current: pos.current ^ pos.mask, mask: pos.mask | move_bit, moves: pos.moves + 1 }
+	}
+	
+	fn play_column(&self, pos:&Position, x:u32) -> Position
+	{
+		self.play(pos, self.possible(pos) & self.column_mask(x))
+	}
+	
+	/// unique key of the position
+	fn key(&self, pos:&Position) -> u64
+	{
+		pos.current.wrapping_add(pos.mask)
+	}
+	
+	/// empty squares, that would complete a row of the given stones
+	fn winning_squares(&self, stones:u64, mask:u64) -> u64
+	{
+		if self.win == 4 && 3*self.shifts[3] < 64 { return self.winning_squares4(stones, mask); } //the unrolled shifts overflow on tall boards
+		let win = self.win as i32;
+		let mut squares = 0u64;
+		for &shift in self.shifts.iter()
+		{
+			for j in 0..win
+			{ //the empty square is the j-th of the row
+				let mut row = !0u64;
+				for i in (0..win).filter(|&i| i != j)
+				{
+					let dist = (i - j) * shift as i32;
+					row &= if dist > 0 { stones.checked_shr(dist as u32).unwrap_or(0) } else { stones.checked_shl((-dist) as u32).unwrap_or(0) };
+				}
+				squares |= row;
+			}
+		}
+		squares & (self.board ^ mask)
+	}
+	
+	/// winning_squares for connect four, unrolled (vertical rows can only be completed on top)
+	fn winning_squares4(&self, stones:u64, mask:u64) -> u64
+	{
+		let mut squares = (stones << 1) & (stones << 2) & (stones << 3);
+		for &shift in self.shifts[1..].iter()
+		{
+			let pair = (stones << shift) & (stones << (2*shift));
+			squares |= pair & (stones << (3*shift));
+			squares |= pair & (stones >> shift);
+			let pair = (stones >> shift) & (stones >> (2*shift));
+			squares |= pair & (stones << shift);
+			squares |= pair & (stones >> (3*shift));
+		}
+		squares & (self.board ^ mask)
+	}
+	
+	fn can_win_next(&self, pos:&Position) -> bool
+	{
+		self.winning_squares(pos.current, pos.mask) & self.possible(pos) != 0
+	}
+	
+	/// moves, that do not let the opponent win directly (0 if every move looses), assuming the player to move can not win directly
+	fn non_losing_moves(&self, pos:&Position) -> u64
+	{
+		let mut possible = self.possible(pos);
+		let opponent_wins = self.winning_squares(pos.current ^ pos.mask, pos.mask);
+		let forced = possible & opponent_wins;
+		if forced != 0
+		{
+			if forced & (forced - 1) != 0 { return 0; } //more than one threat to block
+			possible = forced;
+		}
+		possible & !(opponent_wins >> 1) //do not play below a winning square of the opponent
+	}
+	
+	/// number of winning squares after the move, for move ordering
+	fn move_score(&self, pos:&Position, move_bit:u64) -> u32
+	{
+		self.winning_squares(pos.current | move_bit, pos.mask).count_ones()
+	}
+}
+
+/// solves positions: scores are positive if the player to move wins (the sooner, the higher: 1 for a win with the last
+/// stone of the game), 0 for a draw and negative if he looses (the later, the closer to 0)
+pub struct Solver
+{
+	board: Board,
+	order: Vec<u32>, //columns from the center to the sides
+	keys: Vec<u64>,
+	values: Vec<u8>, //lower or upper bounds of the scores, see tt_get, 0 for empty entries
+	nodes: u64, //number of searched positions
+}
+
+impl Solver
+{
+	/// solver for fields of this size, error if the board does not fit into the bitboards
+	pub fn new(field:&Field) -> Result<Solver, Error>
+	{
+		Solver::with_memory(field, SOLVER_TT_MEMORY)
+	}
+	
+	pub fn with_memory(field:&Field, memory:usize) -> Result<Solver, Error>
+	{
+		let (w, h) = (field.get_w(), field.get_h());
+		if !Solver::is_supported(field)
+		{
+			return Err(Error::InvalidConfig(format!("the solver can not handle a {}x{} board", w, h)));
+		}
+		let num = Solver::prime_below((memory / 9).max(2));
+		let mut order:Vec<u32> = (0..w).collect();
+		order.sort_by_key(|&x| ((2*x as i32 - (w as i32 - 1)).abs(), x));
+		Ok(Solver { board: Board::new(w, h, field.get_win()), order, keys: vec![0; num], values: vec![0; num], nodes: 0 })
+	}
+	
+	/// largest prime number <= n (n >= 2), table sizes should not share factors with the keys
+	fn prime_below(n:usize) -> usize
+	{
+		let is_prime = |k:usize| (2..).take_while(|i| i*i <= k).all(|i| k % i != 0);
+		(2..n+1).rev().find(|&k| is_prime(k)).unwrap_or(2)
+	}
+	
+	/// the bitboards need an additional row for every column
+	pub fn is_supported(field:&Field) -> bool
+	{
+		field.get_w() as usize <= MAX_COLUMNS && field.get_w() * (field.get_h() + 1) <= 64
+	}
+	
+	pub fn get_nodes(&self) -> u64
+	{
+		self.nodes
+	}
+	
+	/// scores are lower than this, used to translate scores into values of the table
+	fn min_score(&self) -> i32
+	{
+		-(self.board.size as i32) / 2 - 1
+	}
+	
+	/// scores are not higher than this
+	fn max_score(&self) -> i32
+	{
+		(self.board.size as i32 + 1) / 2
+	}
+	
+	fn position(&self, field:&Field) -> Position
+	{
+		let h = self.board.h;
+		let p = field.get_player_to_move();
+		let mut pos = Position { current: 0, mask: 0, moves: field.get_turns().len() as u32 };
+		for x in 0..self.board.w
+		{
+			for y in 0..h
+			{
+				let bit = 1u64 << (x*(h+1) + h-1-y); //field rows are counted from the top
+				match field.get_val(x, y)
+				{
+					Cell::Stone(q) =>
+						{
+							pos.mask |= bit;
+							if q == p { pos.current |= bit; }
+						},
+					Cell::Empty => {},
+				}
+			}
+		}
+		pos
+	}
+	
+	/// bound of the score found by an earlier search: (true, lower bound) or (false, upper bound)
+	fn tt_get(&self, key:u64) -> Option<(bool, i32)>
+	{
+		let i = (key % self.keys.len() as u64) as usize;
+		if self.keys[i] != key || self.values[i] == 0 { return None; }
+		let value = self.values[i] as i32;
+		let (min, max) = (self.min_score(), self.max_score());
+		if value > max - min + 1 { Some((true, value + 2*min - max - 2)) } //lower bounds are stored above all upper bounds
+		else { Some((false, value + min - 1)) }
+	}
+	
+	fn tt_put(&mut self, key:u64, lower:bool, score:i32)
+	{
+		let i = (key % self.keys.len() as u64) as usize;
+		let (min, max) = (self.min_score(), self.max_score());
+		self.keys[i] = key;
+		self.values[i] = if lower { score + max - 2*min + 2 } else { score - min + 1 } as u8;
+	}
+	
+	/// null window searches are done with beta = alpha + 1. the player to move must not be able to win directly
+	fn negamax(&mut self, pos:&Position, mut alpha:i32, mut beta:i32) -> i32
+	{
+		self.nodes += 1;
+		let size = self.board.size as i32;
+		let moves = pos.moves as i32;
+		
+		let next = self.board.non_losing_moves(pos);
+		if next == 0 { return -(size - moves) / 2; } //every move lets the opponent win
+		if moves >= size - 2 { return 0; } //nobody can win anymore
+		
+		let min = -(size - 2 - moves) / 2; //the opponent can not win directly
+		if alpha < min
+		{
+			alpha = min;
+			if alpha >= beta { return alpha; }
+		}
+		let mut max = (size - 1 - moves) / 2; //the player can not win directly
+		let key = self.board.key(pos);
+		match self.tt_get(key)
+		{
+			Some((true, lower)) if alpha < lower =>
+				{
+					alpha = lower;
+					if alpha >= beta { return alpha; }
+				},
+			Some((false, upper)) => max = upper,
+			_ => {},
+		}
+		if beta > max
+		{
+			beta = max;
+			if alpha >= beta { return beta; }
+		}
+		
+		//explore moves with more winning squares first (insertion sort, central columns stay first on ties)
+		let mut candidates = [(0u64, 0u32); MAX_COLUMNS];
+		let mut num = 0;
+		for &x in self.order.iter()
+		{
+			let move_bit = next & self.board.column_mask(x);
+			if move_bit == 0 { continue; }
+			let score = self.board.move_score(pos, move_bit);
+			let mut i = num;
+			while i > 0 && candidates[i-1].1 < score
+			{
+				candidates[i] = candidates[i-1];
+				i -= 1;
+			}
+			candidates[i] = (move_bit, score);
+			num += 1;
+		}
+		
+		for &(move_bit, _) in candidates[..num].iter()
+		{
+			let child = self.board.play(pos, move_bit);
+			let score = -self.negamax(&child, -beta, -alpha);
+			if score >= beta
+			{
+				self.tt_put(key, true, score);
+				return score;
+			}
+			if score > alpha { alpha = score; }
+		}
+		self.tt_put(key, false, alpha);
+		alpha
+	}
+	
+	fn solve_position(&mut self, pos:&Position) -> i32
+	{
+		let size = self.board.size as i32;
+		let moves = pos.moves as i32;
+		if self.board.can_win_next(pos) { return (size + 1 - moves) / 2; }
+		if moves >= size - 1 { return 0; } //last stone can not win
+		
+		//narrow the score down with null window searches, starting around 0 to find the outcome fast
+		let mut min = -(size - moves) / 2;
+		let mut max = (size + 1 - moves) / 2;
+		while min < max
+		{
+			let mut med = min + (max - min) / 2;
+			if med <= 0 && min / 2 < med { med = min / 2; }
+			else if med >= 0 && max / 2 > med { med = max / 2; }
+			let r = self.negamax(pos, med, med + 1);
+			if r <= med { max = r; } else { min = r; }
+		}
+		min
+	}
+	
+	/// exact score of the position for the player to move, error if the game is over already
+	pub fn solve(&mut self, field:&Field) -> Result<i32, Error>
+	{
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		let pos = self.position(field);
+		Ok(self.solve_position(&pos))
+	}
+	
+	/// exact scores of every column for the player to move, None for full columns
+	pub fn analyze(&mut self, field:&Field) -> Result<Vec<Option<i32>>, Error>
+	{
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		let pos = self.position(field);
+		let size = self.board.size as i32;
+		let mut scores = Vec::with_capacity(self.board.w as usize);
+		for x in 0..self.board.w
+		{
+			if !self.board.can_play(&pos, x) { scores.push(None); continue; }
+			let move_bit = self.board.possible(&pos) & self.board.column_mask(x);
+			let child = self.board.play(&pos, move_bit);
+			let score = if self.board.winning_squares(pos.current, pos.mask) & move_bit != 0 { (size + 1 - pos.moves as i32) / 2 }
+				else if pos.moves as i32 + 1 >= size { 0 } //last square, no win
+				else { -self.solve_position(&child) };
+			scores.push(Some(score));
+		}
+		Ok(scores)
+	}
+	
+	/// best column for the player to move (central columns preferred among equally good ones) and its score
+	pub fn best_move(&mut self, field:&Field) -> Result<(u32, i32), Error>
+	{
+		if field.get_state().is_over() { return Err(Error::GameOver); }
+		let pos = self.position(field);
+		let size = self.board.size as i32;
+		let target = self.solve_position(&pos);
+		let order = self.order.clone();
+		let mut best = None;
+		for &x in order.iter()
+		{
+			if !self.board.can_play(&pos, x) { continue; }
+			let move_bit = self.board.possible(&pos) & self.board.column_mask(x);
+			let score = if self.board.winning_squares(pos.current, pos.mask) & move_bit != 0 { (size + 1 - pos.moves as i32) / 2 }
+				else if pos.moves as i32 + 1 >= size { 0 }
+				else
+				{ //only check if the move reaches the score of the position
+					let child = self.board.play(&pos, move_bit);
+					if self.board.can_win_next(&child) { -(size - pos.moves as i32) / 2 } //opponent wins directly
+					else { -self.negamax(&child, -target, -target + 1) }
+				};
+			if score >= target { return Ok((x, target)); }
+			if best.is_none_or(|(_, s)| score > s) { best = Some((x, score)); }
+		}
+		Ok(best.expect("Running game without a possible move!"))
+	}
+	
+	/// describes a score, e.g. for the analysis output
+	pub fn describe(&self, field:&Field, score:i32) -> String
+	{
+		let left = (self.board.size - field.get_turns().len() as u32) as i32; //free squares
+		if score > 0 { format!("win with own stone {}", (left + 3) / 2 - score) }
+		else if score < 0 { format!("loss with stone {} of the opponent", (left + 2) / 2 + score) }
+		else { "draw".to_string() }
+	}
+}
+
+
+#[cfg(test)]
+mod tests
+{
+	use super::{Solver, Board};
+	use super::super::field::{Field, GameState};
+	
+	/// pseudo random numbers, so that the tested positions are the same in every run
+	fn next_random(state:&mut u64) -> u64
+	{
+		*state ^= *state << 13;
+		*state ^= *state >> 7;
+		*state ^= *state << 17;
+		*state
+	}
+	
+	/// random running position with the given number of stones
+	fn random_position(w:u32, h:u32, win:u32, stones:u32, rng:&mut u64) -> Field
+	{
+		loop
+		{
+			let mut field = Field::new(w, h, win);
+			while field.get_turns().len() < stones as usize && !field.get_state().is_over()
+			{
+				let x = (next_random(rng) % w as u64) as u32;
+				let p = field.get_player_to_move();
+				let _ = field.play(p, x); //full columns are tried again
+			}
+			if !field.get_state().is_over() { return field; }
+		}
+	}
+	
+	/// score of the position by plain negamax over all moves, counted like the solver does
+	fn brute_force(field:&mut Field) -> i32
+	{
+		let size = field.get_size() as i32;
+		let moves = field.get_turns().len() as i32;
+		let p = field.get_player_to_move();
+		let mut best = None;
+		for x in 0..field.get_w()
+		{
+			if field.play(p, x).is_err() { continue; }
+			let score = match field.get_state()
+				{
+					GameState::Win(_) => (size + 1 - moves) / 2,
+					GameState::Draw => 0,
+					GameState::Running => -brute_force(field),
+				};
+			field.undo();
+			best = Some(best.map_or(score, |best:i32| best.max(score)));
+		}
+		best.unwrap()
+	}
+	
+	#[test]
+	fn solve_matches_brute_force()
+	{
+		let mut rng = 0x2545F4914F6CDD1D;
+		for &(w, h, win) in [(3, 3, 3), (3, 4, 3), (4, 4, 3), (4, 4, 4), (5, 4, 4), (4, 5, 4), (5, 3, 3)].iter()
+		{
+			let size = w * h;
+			for i in 0..10
+			{
+				let stones = if size <= 9 { i % (size - 1) } else { size - 8 - i % 2 }; //brute force only near the end on the larger boards
+				let mut field = random_position(w, h, win, stones, &mut rng);
+				let expected = brute_force(&mut field);
+				let mut solver = Solver::with_memory(&field, 1024 * 1024).unwrap();
+				assert_eq!(solver.solve(&field).unwrap(), expected, "{}x{}, win {}, moves {}", w, h, win, field.to_moves());
+				
+				//the scores of the moves and the best move agree with the score of the position
+				let scores = solver.analyze(&field).unwrap();
+				assert_eq!(scores.iter().filter_map(|&score| score).max().unwrap(), expected);
+				let (x, score) = solver.best_move(&field).unwrap();
+				assert_eq!(score, expected);
+				assert_eq!(scores[x as usize], Some(expected));
+			}
+		}
+	}
+	
+	#[test]
+	fn known_small_boards_are_draws()
+	{
+		for &(w, h) in [(4, 4), (5, 4), (4, 5)].iter()
+		{
+			let field = Field::new(w, h, 4);
+			assert_eq!(Solver::new(&field).unwrap().solve(&field).unwrap(), 0, "{}x{}", w, h);
+		}
+	}
+	
+	#[test]
+	fn direct_wins_and_forced_losses()
+	{
+		let size = 42;
+		//X wins with its 4th stone in column 1
+		let field = Field::from_moves("121212").unwrap();
+		let mut solver = Solver::new(&field).unwrap();
+		assert_eq!(solver.solve(&field).unwrap(), (size + 1 - 6) / 2);
+		assert_eq!(solver.best_move(&field).unwrap().0, 0);
+		
+		//X threatens both ends of its row at the bottom, O can only block one of them
+		let field = Field::from_moves("4455334").unwrap();
+		assert_eq!(solver.solve(&field).unwrap(), -(size - 7) / 2);
+	}
+	
+	#[test]
+	#[ignore] //takes very long, run with cargo test --release -- --ignored
+	fn empty_standard_board_is_a_first_player_win()
+	{
+		let field = Field::new(7, 6, 4);
+		assert!(Solver::new(&field).unwrap().solve(&field).unwrap() > 0);
+	}
+	
+	/// empty squares, that complete a row of the stones, found by counting the stones in every direction
+	fn winning_squares_brute_force(board:&Board, stones:u64, mask:u64) -> u64
+	{
+		let (w, h, win) = (board.w as i32, board.h as i32, board.win as i32);
+		let is_stone = |x:i32, y:i32| x >= 0 && x < w && y >= 0 && y < h && stones & (1u64 << (x*(h+1) + y)) != 0;
+		let mut squares = 0u64;
+		for x in 0..w
+		{
+			for y in 0..h
+			{
+				let bit = 1u64 << (x*(h+1) + y);
+				if mask & bit != 0 { continue; }
+				for &(dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)].iter()
+				{
+					let mut count = 1;
+					for &sign in [1, -1].iter()
+					{
+						let mut i = 1;
+						while is_stone(x + sign*i*dx, y + sign*i*dy) { count += 1; i += 1; }
+					}
+					if count >= win { squares |= bit; }
+				}
+			}
+		}
+		squares
+	}
+	
+	#[test]
+	fn winning_squares_on_all_board_shapes()
+	{
+		let mut rng = 0x9E3779B97F4A7C15;
+		for &(w, h, win) in [(7, 6, 4), (4, 4, 4), (9, 5, 4), (5, 9, 4), (3, 20, 4), (2, 31, 4), (21, 2, 4), (6, 7, 3), (8, 7, 5)].iter()
+		{
+			let board = Board::new(w, h, win);
+			for _ in 0..50
+			{ //stones lie on top of each other, the empty squares above them
+				let (mut stones, mut mask) = (0u64, 0u64);
+				for x in 0..w
+				{
+					for y in 0..(next_random(&mut rng) % (h as u64 + 1)) as u32
+					{
+						let bit = 1u64 << (x*(h+1) + y);
+						mask |= bit;
+						if next_random(&mut rng) % 3 != 0 { stones |= bit; }
+					}
+				}
+				assert_eq!(board.winning_squares(stones, mask), winning_squares_brute_force(&board, stones, mask), "{}x{}, win {}", w, h, win);
+			}
+		}
+	}
+	
+	#[test]
+	fn tall_boards_are_supported()
+	{
+		for &(w, h) in [(3, 20), (2, 31)].iter()
+		{
+			let mut field = Field::new(w, h, 4);
+			field.play_moves("121212").unwrap();
+			assert!(Solver::is_supported(&field));
+			let mut solver = Solver::new(&field).unwrap();
+			assert_eq!(solver.solve(&field).unwrap(), (w as i32 * h as i32 + 1 - 6) / 2, "{}x{}", w, h); //X completes the column
+		}
+	}
+}
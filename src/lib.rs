@@ -0,0 +1,12 @@
+//! Connect four with classic and self-learning AI players.
+//!
+//! `game::field` holds the board and the rules, `game::player` the `Player` trait, the built-in players and
+//! the model files of the learning players, `Game` plays them against each other. `game::solver` computes
+//! the exact outcome of positions. `game::config` holds the settings of the players,
+//! `game::context` what they know about the game besides the position (history, clocks, match).
+
+pub mod game;
+
+pub use game::{Game, GameContext, PlayerType, PlayerSpec, IllegalMovePolicy, Config, Error, Solver};
+pub use game::field::{Field, PlayerId, Cell, GameState, PositionError};
+pub use game::player::{Player, Analyze, SearchInfo};
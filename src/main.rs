@@ -1,111 +1,609 @@
-mod game;
+extern crate connect_four;
+extern crate rand;
+extern crate ctrlc;
 
-use game::*;
-use std::time::Instant;
+use connect_four::game::*;
+use connect_four::game::player::PlayerAlphaZero;
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use rand::Rng;
+
+
+const USAGE:&str = "Usage: connect_four <command> [--option value]..
+
+Commands:
+  play        play games between two players
+              --p1 <player> (io), --p2 <player> (aivalue), --games <n> (2), --gps <n> (1), --start <x|o> (x),
+              --info <yes|no> (no): print depth, score, nodes, principal variation, .. of every searched move
+  train       train a learning player, optionally testing its opponent after every round
+              --player <player> (aivalue), --opponent <player> (aivaluefixed), --games <n> (100), --gps <n> (10),
+              --rounds <n> (1), --test <player>, --test-games <n> (2), --start <x|o> (x)
+  selfplay    train the alphazero player by playing against itself, optionally testing it after every round
+              --player <alphazero player> (alphazero), --games <n> (100), --rounds <n> (1), --test <player>,
+              --test-games <n> (2), --start <x|o> (x)
+  eval        evaluate a player against one or more opponents
+              --player <player> (aivaluefixed), --opponents <player,..> (random), --games <n> (1000), --gps <n> (1),
+              --start <x|o> (x), --oracle <n> (0): share of perfect moves in n random positions, judged by the solver
+  tournament  round robin between players
+              --players <player,..> (random,minimax), --games <n> (10), --gps <n> (1), --start <x|o> (x)
+  analyze     show a position with its legal, winning and forced moves and the solver's score of every move
+  solve       compute the outcome and the best move of a position with perfect play
+              (analyze and solve can take very long for positions early in the game on large boards)
+  help        show this message
+
+Rules and position (all commands):
+  --width <n> (7), --height <n> (6), --win <n> (4), --moves <columns, like 4453>, --diagram <file> (analyze, solve)
+  --move-time <ms> (play, train, selfplay, eval, tournament): time per move of minimax, aivalue, mcts and alphazero instead of a fixed search depth or iteration count
+  --game-time <ms> (play, train, eval, tournament): thinking time of every player per game, exceeding it loses the game.
+              the searching players share it out over their moves
+  --autosave <n> (play, train, selfplay, eval, tournament): save the learning players every n games (0: only at the end),
+              they are also saved when Ctrl-C stops the games (after the current move, press it twice to quit at once)
+  --models <dir> (play, train, selfplay, eval, tournament): directory of the model files of the learning players (current directory),
+              named like AIValue-7x6.NN. a player can use another file with its model setting, like aivaluefixed:model=runs/a.NN
+
+Players: io, random, minimax, aiq, aiqfixed, aiqplay, aivalue, aivaluefixed, solver, mcts, mctsrandom, alphazero, alphazerofixed
+  settings are appended like minimax:depth=8:tt_memory=0, defaults can be changed with
+  --config <file> (play, train, selfplay, eval, tournament): TOML (.toml) or JSON file with a table for every player type,
+  like [minimax] depth = 8 or {\"minimax\": {\"depth\": 8}}, the types use the sections
+  minimax, aivalue, aiq, aiqoff, mcts, alphazero and solver";
+
+const RULE_OPTIONS:[&str; 4] = ["width", "height", "win", "moves"];
+
+
+#[derive(Debug)]
+enum CliError
+{
+	Usage(String), //description of the invalid command line
+	Game(Error),
+}
+
+impl fmt::Display for CliError
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			CliError::Usage(ref reason) => write!(f, "{}\n\n{}", reason, USAGE),
+			CliError::Game(ref err) => write!(f, "{}", err),
+		}
+	}
+}
+
+impl From<Error> for CliError
+{
+	fn from(err:Error) -> CliError
+	{
+		CliError::Game(err)
+	}
+}
+
+impl From<PositionError> for CliError
+{
+	fn from(err:PositionError) -> CliError
+	{
+		CliError::Game(Error::Position(err))
+	}
+}
+
+
+/// named options of a command (--name value)
+struct Options
+{
+	values: HashMap<String, String>,
+	interrupt: Arc<AtomicBool>, //set by Ctrl-C
+}
+
+impl Options
+{
+	/// parses the options, allowed are the rule options and the given command specific ones
+	fn parse(args:&[String], allowed:&[&str]) -> Result<Options, CliError>
+	{
+		let mut values = HashMap::new();
+		let mut iter = args.iter();
+		while let Some(arg) = iter.next()
+		{
+			if !arg.starts_with("--") { return Err(CliError::Usage(format!("unexpected argument \"{}\"", arg))); }
+			let name = arg[2..].to_lowercase();
+			if !allowed.contains(&&name[..]) && !RULE_OPTIONS.contains(&&name[..])
+			{
+				return Err(CliError::Usage(format!("unknown option \"{}\"", arg)));
+			}
+			match iter.next()
+			{
+				Some(value) => { values.insert(name, value.trim().to_string()); },
+				None => return Err(CliError::Usage(format!("option \"{}\" needs a value", arg))),
+			}
+		}
+		Ok(Options { values, interrupt: Arc::new(AtomicBool::new(false)) })
+	}
+	
+	fn get_str(&self, name:&str) -> Option<&str>
+	{
+		self.values.get(name).map(|value| &value[..])
+	}
+	
+	fn get_u32(&self, name:&str, default:u32) -> Result<u32, CliError>
+	{
+		match self.get_str(name)
+		{
+			Some(value) => value.parse::<u32>()
+				.map_err(|_| CliError::Usage(format!("invalid value \"{}\" for option \"--{}\", expected a number", value, name))),
+			None => Ok(default),
+		}
+	}
+	
+	fn get_bool(&self, name:&str, default:bool) -> Result<bool, CliError>
+	{
+		match self.get_str(name).map(|value| value.to_lowercase())
+		{
+			Some(ref value) if value == "yes" || value == "true" || value == "1" => Ok(true),
+			Some(ref value) if value == "no" || value == "false" || value == "0" => Ok(false),
+			Some(value) => Err(CliError::Usage(format!("invalid value \"{}\" for option \"--{}\", expected yes or no", value, name))),
+			None => Ok(default),
+		}
+	}
+	
+	/// lets Ctrl-C stop the games after the current move, so that the learning players are saved. a second Ctrl-C quits at once
+	fn catch_interrupts(&self)
+	{
+		let flag = self.interrupt.clone();
+		let result = ctrlc::set_handler(move ||
+			{
+				if flag.swap(true, Ordering::SeqCst) { process::exit(130); }
+				println!("\nInterrupted, stopping after the current move..");
+			});
+		if let Err(err) = result { println!("Warning: Ctrl-C will not save the players: {}", err); }
+	}
+	
+	fn is_interrupted(&self) -> bool
+	{
+		self.interrupt.load(Ordering::SeqCst)
+	}
+	
+	/// games per side, before switching the start player
+	fn get_gps(&self, default:u32) -> Result<u32, CliError>
+	{
+		match self.get_u32("gps", default)?
+		{
+			0 => Err(CliError::Usage("option \"--gps\" has to be at least 1".to_string())),
+			gps => Ok(gps),
+		}
+	}
+	
+	/// settings of the players from the --config file, the defaults without it
+	fn get_config(&self) -> Result<Config, CliError>
+	{
+		match self.get_str("config")
+		{
+			Some(filename) => Ok(Config::load(filename)?),
+			None => Ok(Config::default()),
+		}
+	}
+	
+	fn get_player(&self, name:&str, default:&str) -> Result<PlayerSpec, CliError>
+	{
+		parse_player(self.get_str(name).unwrap_or(default), &self.get_config()?)
+	}
+	
+	/// player of an option without default
+	fn get_optional_player(&self, name:&str) -> Result<Option<PlayerSpec>, CliError>
+	{
+		match self.get_str(name)
+		{
+			Some(player) => Ok(Some(parse_player(player, &self.get_config()?)?)),
+			None => Ok(None),
+		}
+	}
+	
+	/// comma separated list of players
+	fn get_players(&self, name:&str, default:&str) -> Result<Vec<PlayerSpec>, CliError>
+	{
+		let config = self.get_config()?;
+		self.get_str(name).unwrap_or(default).split(',')
+			.map(|player| parse_player(player.trim(), &config))
+			.collect()
+	}
+	
+	fn get_start(&self) -> Result<PlayerId, CliError>
+	{
+		match &self.get_str("start").unwrap_or("x").to_lowercase()[..]
+		{
+			"x" | "1" | "p1" => Ok(PlayerId::P1),
+			"o" | "2" | "p2" => Ok(PlayerId::P2),
+			value => Err(CliError::Usage(format!("invalid start player \"{}\", expected x or o", value))),
+		}
+	}
+	
+	/// game with the rules and opening of the options
+	fn create_game(&self) -> Result<Game, CliError>
+	{
+		let mut game = Game::with_rules(self.get_u32("width", DEFAULT_WIDTH)?, self.get_u32("height", DEFAULT_HEIGHT)?, self.get_u32("win", DEFAULT_WIN)?)?;
+		game.set_opening(self.get_str("moves").unwrap_or(""))?;
+		game.set_move_time(self.get_move_time()?);
+		game.set_print_info(self.get_bool("info", false)?);
+		if self.get_str("game-time").is_some() { game.set_game_time(Some(Duration::from_millis(self.get_u32("game-time", 0)? as u64))); }
+		game.set_autosave(self.get_u32("autosave", 0)?);
+		game.set_interrupt(self.interrupt.clone());
+		game.set_model_dir(self.get_str("models").unwrap_or(""));
+		Ok(game)
+	}
+	
+	fn get_move_time(&self) -> Result<Option<Duration>, CliError>
+	{
+		match self.get_str("move-time")
+		{
+			Some(_) => Ok(Some(Duration::from_millis(self.get_u32("move-time", 0)? as u64))),
+			None => Ok(None),
+		}
+	}
+	
+	/// field with the rules of the options and the position of --moves or --diagram
+	fn create_field(&self) -> Result<Field, CliError>
+	{
+		let (width, height, win) = (self.get_u32("width", DEFAULT_WIDTH)?, self.get_u32("height", DEFAULT_HEIGHT)?, self.get_u32("win", DEFAULT_WIN)?);
+		if !Field::is_supported(width, height, win) { return Err(CliError::Game(Error::UnsupportedRules(width, height, win))); }
+		let mut field = Field::new(width, height, win);
+		match (self.get_str("moves"), self.get_str("diagram"))
+		{
+			(Some(_), Some(_)) => return Err(CliError::Usage("use either --moves or --diagram".to_string())),
+			(Some(moves), None) => field.play_moves(moves)?,
+			(None, Some(filename)) => {
+					let mut diagram = String::new();
+					File::open(filename).and_then(|mut file| file.read_to_string(&mut diagram)).map_err(Error::Io)?;
+					field.load_diagram(&diagram)?;
+				},
+			(None, None) => {},
+		}
+		Ok(field)
+	}
+}
 
 
-#[allow(unreachable_code)]
 fn main()
 {
-	match 55
-	{
-		0 => general_play(PlayerType::IO, PlayerType::AIValue, 2, 1, true), //play against IO + learn
-		1 => general_play(PlayerType::IO, PlayerType::AIValueFixed, 2, 1, true), //test with IO
-		2 => general_play(PlayerType::Minimax, PlayerType::AIValueFixed, 100, 1, true), //test with minimax
-		3 => general_play(PlayerType::Random, PlayerType::AIValueFixed, 1000, 1, true), //test with random
-		4 => general_play(PlayerType::AIValueFixed, PlayerType::AIValue, 1_000, 10, true), //training
-		5 => { //continuous training and testing
-				println!("Training:");
-				for i in 0..100
-				{
-					println!("Training {}:", i+1);
-					general_play(PlayerType::AIValueFixed, PlayerType::AIValue, 100, 10, true); //train, learn
-					println!("Test {}:", i+1);
-					general_play(PlayerType::Minimax, PlayerType::AIValueFixed, 2, 1, true); //test with minimax
-				}
-				println!("Testing:");
-				general_play(PlayerType::Random, PlayerType::AIValueFixed, 1000, 1, true); //test with random
-				general_play(PlayerType::IO, PlayerType::AIValueFixed, 2, 1, true); //test with IO
-			},
-		_ => {
-				//general playing with command line arguments
-				play_from_args();
+	let args:Vec<String> = env::args().skip(1).collect(); //skip program name
+	let result = match args.first().map(|cmd| cmd.to_lowercase())
+	{
+		Some(ref cmd) if cmd == "play" => cmd_play(&args[1..]),
+		Some(ref cmd) if cmd == "train" => cmd_train(&args[1..]),
+		Some(ref cmd) if cmd == "selfplay" => cmd_selfplay(&args[1..]),
+		Some(ref cmd) if cmd == "eval" => cmd_eval(&args[1..]),
+		Some(ref cmd) if cmd == "tournament" => cmd_tournament(&args[1..]),
+		Some(ref cmd) if cmd == "analyze" => cmd_analyze(&args[1..]),
+		Some(ref cmd) if cmd == "solve" => cmd_solve(&args[1..]),
+		Some(ref cmd) if cmd == "help" || cmd == "--help" || cmd == "-h" => { println!("{}", USAGE); Ok(()) },
+		Some(cmd) => Err(CliError::Usage(format!("unknown command \"{}\"", cmd))),
+		None => Err(CliError::Usage("no command given".to_string())),
+	};
+	
+	if let Err(err) = result
+	{
+		println!("Error: {}", err);
+		process::exit(1);
+	}
+}
+
+fn cmd_play(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["p1", "p2", "games", "gps", "start", "move-time", "game-time", "autosave", "models", "config", "info"])?;
+	let p1 = options.get_player("p1", "io")?;
+	let p2 = options.get_player("p2", "aivalue")?;
+	let num = options.get_u32("games", 2)?;
+	let gps = options.get_gps(1)?;
+	let startp = options.get_start()?;
+	
+	options.catch_interrupts();
+	let game = options.create_game()?;
+	general_play(game, &p1, &p2, num, gps, startp)?;
+	Ok(())
+}
+
+/// trains in rounds, the opponent plays X (usually the fixed version of the learning player), the learning player O
+fn cmd_train(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["player", "opponent", "games", "gps", "rounds", "test", "test-games", "start", "move-time", "game-time", "autosave", "models", "config"])?;
+	let player = options.get_player("player", "aivalue")?;
+	let opponent = options.get_player("opponent", "aivaluefixed")?;
+	let num = options.get_u32("games", 100)?;
+	let gps = options.get_gps(10)?;
+	let rounds = options.get_u32("rounds", 1)?;
+	let test = options.get_optional_player("test")?;
+	let test_games = options.get_u32("test-games", 2)?;
+	let startp = options.get_start()?;
+	
+	options.catch_interrupts();
+	for i in 0..rounds
+	{
+		println!("Training {}:", i+1);
+		let mut game = options.create_game()?;
+		game.set_training(true);
+		general_play(game, &opponent, &player, num, gps, startp)?; //train, learn
+		if let Some(ref test) = test
+		{
+			println!("Test {}:", i+1);
+			general_play(options.create_game()?, test, &opponent, test_games, 1, startp)?;
+		}
+	}
+	Ok(())
+}
+
+/// trains the alphazero player in rounds of games against itself (starting with --moves), the test opponent plays X
+fn cmd_selfplay(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["player", "games", "rounds", "test", "test-games", "start", "move-time", "autosave", "models", "config"])?;
+	let player = options.get_player("player", "alphazero")?;
+	if player.ptype != PlayerType::AlphaZero { return Err(CliError::Usage("self-play needs the alphazero player".to_string())); }
+	let num = options.get_u32("games", 100)?;
+	let rounds = options.get_u32("rounds", 1)?;
+	let test = options.get_optional_player("test")?;
+	let test_games = options.get_u32("test-games", 2)?;
+	let startp = options.get_start()?;
+	let field = options.create_field()?;
+	let autosave = options.get_u32("autosave", 0)?;
+	
+	options.catch_interrupts();
+	for i in 0..rounds
+	{
+		println!("Self-play {}:", i+1);
+		println!("Playing {} games..", num);
+		let now = Instant::now();
+		{ //the model is saved before the player is tested
+			let mut ai = PlayerAlphaZero::with_config(false, player.config.alphazero.clone());
+			ai.set_time_limit(options.get_move_time()?);
+			ai.set_model_dir(options.get_str("models").unwrap_or(""));
+			ai.init(&field, field.get_player_to_move(), &GameContext::new(&field))?;
+			for g in 0..num
+			{ //one game at a time to stop on Ctrl-C
+				if options.is_interrupted() { break; }
+				ai.self_play(&field, 1)?;
+				if autosave > 0 && (g+1) % autosave == 0 { ai.save()?; }
 			}
+			ai.save()?;
+		}
+		if options.is_interrupted()
+		{
+			println!("Interrupted, the model was saved.");
+			return Err(CliError::Game(Error::Interrupted));
+		}
+		let elapsed = now.elapsed();
+		let sec = (elapsed.as_secs() as f64) + (elapsed.subsec_nanos() as f64 / 1_000_000_000.0);
+		println!("Time: {} min {:.3} s", (sec / 60.0).floor(), sec % 60.0);
+		println!();
+		if let Some(ref test) = test
+		{
+			println!("Test {}:", i+1);
+			let fixed = PlayerSpec::with_config(PlayerType::AlphaZeroFixed, player.config.clone());
+			general_play(options.create_game()?, test, &fixed, test_games, 1, startp)?;
+		}
 	}
+	Ok(())
 }
 
+/// the opponents play X, the evaluated player O
+fn cmd_eval(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["player", "opponents", "games", "gps", "start", "move-time", "game-time", "autosave", "models", "oracle", "config"])?;
+	let player = options.get_player("player", "aivaluefixed")?;
+	let opponents = options.get_players("opponents", "random")?;
+	let num = options.get_u32("games", 1000)?;
+	let gps = options.get_gps(1)?;
+	let startp = options.get_start()?;
+	let oracle = options.get_u32("oracle", 0)?;
+	
+	options.catch_interrupts();
+	let mut results = Vec::new();
+	for opponent in opponents.iter()
+	{
+		let (_, p2w) = general_play(options.create_game()?, opponent, &player, num, gps, startp)?;
+		results.push((opponent, p2w));
+	}
+	
+	println!("Evaluation of {}:", player);
+	for &(opponent, winrate) in results.iter()
+	{
+		println!("vs {:<14} {:>6.2}% wins", format!("{}:", opponent), winrate);
+	}
+	if oracle > 0
+	{
+		let (perfect, tested) = oracle_test(&options, &player, oracle)?;
+		println!("Perfect moves: {:>6.2}% ({}/{})", perfect as f64 / tested as f64 * 100.0, perfect, tested);
+	}
+	Ok(())
+}
 
-#[allow(dead_code)]
-fn play_from_args()
+/// lets the player choose moves in random positions (a third to two thirds of the board filled) and counts the moves,
+/// that keep the best possible outcome (win, draw or loss) according to the solver (illegal moves count as mistakes). returns (perfect, tested)
+fn oracle_test(options:&Options, player:&PlayerSpec, num:u32) -> Result<(u32, u32), CliError>
 {
-	let args = env::args();
-	//general playing with command line arguments
-	let mut p1 = PlayerType::IO;
-	let mut p2 = PlayerType::AIValue;
-	let mut num = 2;
-	let mut player1starts = true;
+	let game = options.create_game()?;
+	let start = options.create_field()?;
+	let mut solver = Solver::new(&start)?;
+	let mut rng = rand::thread_rng();
+	println!("Judging {} moves of {} with the solver..", num, player);
 	
-	for (i, arg) in args.enumerate()
+	//one player for each side, the player to move in the start position began the game
+	let first = start.get_player_to_move();
+	let mut players = Vec::new();
+	for &p in [PlayerId::P1, PlayerId::P2].iter()
 	{
-		let param = arg.trim().to_lowercase();
-		match i
+		let mut ai = game.create_player(player).ok_or_else(|| CliError::Usage("no player to evaluate".to_string()))?;
+		ai.init(&start, p, &GameContext::new(&start))?;
+		ai.startp(if start.get_turns().len() % 2 == 0 { first } else { first.other() });
+		players.push(ai);
+	}
+	
+	let (mut perfect, mut tested) = (0, 0);
+	let size = start.get_size() as usize;
+	while tested < num
+	{
+		let mut field = start.clone();
+		let stones = size / 3 + rng.gen::<usize>() % (size / 3 + 1);
+		while field.get_turns().len() < stones && !field.get_state().is_over()
 		{
-			1 => {
-					let player = string_to_player(&param);
-					if player.is_some() { p1 = player.unwrap(); }
-				},
-			2 => {
-					let player = string_to_player(&param);
-					if player.is_some() { p2 = player.unwrap(); }
-				},
-			3 => {
-					let parsed = param.parse::<u32>();
-					if parsed.is_ok() { num = parsed.unwrap(); }
-				},
-			4 => {
-					if param == "false" { player1starts = false; }
-				},
-			_ => {}, //ignore first and all other args
+			let x = rng.gen::<u32>() % field.get_w();
+			let p = field.get_player_to_move();
+			let _ = field.play(p, x); //full columns are just tried again
 		}
+		if field.get_state().is_over() { continue; }
+		
+		let scores = solver.analyze(&field)?;
+		let best = scores.iter().filter_map(|&score| score).max().unwrap_or(0);
+		let p = field.get_player_to_move();
+		let x = players[p.index()].play(&field, &GameContext::new(&field))?;
+		let score = scores.get(x as usize).and_then(|&score| score); //None for illegal moves, they are not perfect
+		if score.is_some_and(|score| score.signum() == best.signum()) { perfect += 1; }
+		tested += 1;
 	}
+	Ok((perfect, tested))
+}
+
+/// every player plays every other player, scores are wins + half the draws
+fn cmd_tournament(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["players", "games", "gps", "start", "move-time", "game-time", "autosave", "models", "config"])?;
+	let players = options.get_players("players", "random,minimax")?;
+	let num = options.get_u32("games", 10)?;
+	let gps = options.get_gps(1)?;
+	let startp = options.get_start()?;
+	if players.len() < 2 { return Err(CliError::Usage("a tournament needs at least 2 players".to_string())); }
 	
-	println!("Running:");
-	general_play(p1, p2, num, 1, player1starts);
+	options.catch_interrupts();
+	let mut scores = vec![0.0; players.len()];
+	for i in 0..players.len()
+	{
+		for j in i+1..players.len()
+		{
+			let (p1w, p2w) = general_play(options.create_game()?, &players[i], &players[j], num, gps, startp)?;
+			let draws = 100.0 - p1w - p2w;
+			scores[i] += (p1w + draws / 2.0) * num as f64 / 100.0;
+			scores[j] += (p2w + draws / 2.0) * num as f64 / 100.0;
+		}
+	}
+	
+	let mut ranking:Vec<usize> = (0..players.len()).collect();
+	ranking.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap());
+	let max = (num as usize * (players.len()-1)) as f64;
+	println!("Standings:");
+	for (rank, &i) in ranking.iter().enumerate()
+	{
+		println!("{:>2}. {:<14} {:>7.1} / {}", rank+1, players[i].to_string(), scores[i], max);
+	}
+	Ok(())
+}
+
+fn cmd_analyze(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["diagram"])?;
+	let mut field = options.create_field()?;
+	
+	field.print();
+	println!();
+	println!("Moves: {}", if field.get_turns().is_empty() { "-".to_string() } else { field.to_moves() });
+	match field.get_state()
+	{
+		GameState::Win(p) => { println!("Player {} has won.", p); return Ok(()); },
+		GameState::Draw => { println!("The game is a draw."); return Ok(()); },
+		GameState::Running => {},
+	}
+	
+	let p = field.get_player_to_move();
+	let legal:Vec<u32> = (0..field.get_w()).filter(|&x| field.is_valid_play(x)).collect();
+	let wins:Vec<u32> = legal.iter().cloned().filter(|&x| is_winning_move(&mut field, p, x)).collect();
+	let threats:Vec<u32> = legal.iter().cloned().filter(|&x| is_winning_move(&mut field, p.other(), x)).collect();
+	println!("Player to move: {}", p);
+	println!("Legal moves: {}", columns_to_string(&legal));
+	println!("Winning moves: {}", columns_to_string(&wins));
+	println!("Moves to block: {}", columns_to_string(&threats));
+	
+	if !Solver::is_supported(&field)
+	{
+		println!("The board is too large for the solver.");
+		return Ok(());
+	}
+	let mut solver = Solver::new(&field)?;
+	println!("Solver scores (positive: win, 0: draw, negative: loss):");
+	for (x, score) in solver.analyze(&field)?.into_iter().enumerate()
+	{
+		if let Some(score) = score
+		{
+			println!("Column {}: {:>3} ({})", x+1, score, solver.describe(&field, score));
+		}
+	}
+	Ok(())
+}
+
+fn cmd_solve(args:&[String]) -> Result<(), CliError>
+{
+	let options = Options::parse(args, &["diagram"])?;
+	let field = options.create_field()?;
+	if field.get_state().is_over() { return Err(CliError::Game(Error::GameOver)); }
+	
+	field.print();
+	println!();
+	let p = field.get_player_to_move();
+	let mut solver = Solver::new(&field)?;
+	let now = Instant::now();
+	let (x, score) = solver.best_move(&field)?;
+	let elapsed = now.elapsed();
+	let sec = (elapsed.as_secs() as f64) + (elapsed.subsec_nanos() as f64 / 1_000_000_000.0);
+	
+	println!("Player {} to move: {} (score {}), best column {}", p, solver.describe(&field, score), score, x+1);
+	println!("Positions: {}", solver.get_nodes());
+	println!("Time: {} min {:.3} s", (sec / 60.0).floor(), sec % 60.0);
+	Ok(())
+}
+
+fn is_winning_move(field:&mut Field, p:PlayerId, x:u32) -> bool
+{
+	if field.play(p, x).is_err() { return false; }
+	let won = field.get_state() == GameState::Win(p);
+	field.undo();
+	won
 }
 
-#[allow(dead_code)]
-fn general_play(p1:PlayerType, p2:PlayerType, num:u32, gps:u32, player1starts:bool)
+/// 1-based column numbers
+fn columns_to_string(columns:&[u32]) -> String
 {
-	println!("Player X: {:?}", p1);
-	println!("Player O: {:?}", p2);
+	if columns.is_empty() { return "-".to_string(); }
+	columns.iter().map(|x| (x+1).to_string()).collect::<Vec<String>>().join(" ")
+}
+
+
+/// plays num games and prints the results, returns the win rates of player 1 and 2 in percent
+fn general_play(mut game:Game, p1:&PlayerSpec, p2:&PlayerSpec, num:u32, gps:u32, startp:PlayerId) -> Result<(f64, f64), Error>
+{
+	println!("Player X: {}", p1);
+	println!("Player O: {}", p2);
 	println!("Playing {} games..", num);
 	
 	//prepare
-	let mut game = Game::new();
-	game.set_start_player(if player1starts {1} else {2});
-	game.set_player1(p1);
-	game.set_player2(p2);
+	game.set_start_player(startp);
+	game.set_player1(p1.clone())?;
+	game.set_player2(p2.clone())?;
 	
 	//measure time
 	let now = Instant::now();
-	let (p1w, p2w) = game.play_many(num, gps); //play
+	let result = game.play_many(num, gps); //play
+	
+	//save worse player first in case 2 learning agents play against each other and use the same file, also when interrupted
+	let first = match result { Ok((p1w, p2w)) if p1w > p2w => PlayerId::P2, _ => PlayerId::P1 };
+	game.save_player(first)?;
+	game.save_player(first.other())?;
+	if let Err(Error::Interrupted) = result { println!("Interrupted, the learning players were saved."); }
+	let (p1w, p2w) = result?;
+	
 	let elapsed = now.elapsed();
 	let sec = (elapsed.as_secs() as f64) + (elapsed.subsec_nanos() as f64 / 1000_000_000.0);
 	println!("Time: {} min {:.3} s", (sec / 60.0).floor(), sec % 60.0);
 	println!("");
-	
-	//drop worse player first in case 2 learning agents play against each other and use the same file
-	if p1w > p2w
-	{
-		game.set_player2(PlayerType::None);
-		game.set_player1(PlayerType::None);
-	}
-	else
-	{
-		game.set_player1(PlayerType::None);
-		game.set_player2(PlayerType::None);
-	}
+	Ok((p1w, p2w))
 }
 
 fn string_to_player(str:&str) -> Option<PlayerType>
@@ -120,8 +618,33 @@ fn string_to_player(str:&str) -> Option<PlayerType>
 		"aiqplay" => Some(PlayerType::AIQPlay),
 		"aivalue" => Some(PlayerType::AIValue),
 		"aivaluefixed" => Some(PlayerType::AIValueFixed),
+		"solver" => Some(PlayerType::Solver),
+		"mcts" => Some(PlayerType::MCTS),
+		"mctsrandom" => Some(PlayerType::MCTSRandom),
+		"alphazero" => Some(PlayerType::AlphaZero),
+		"alphazerofixed" => Some(PlayerType::AlphaZeroFixed),
 		//"aiqoff" => Some(PlayerType::AIQOff),
 		//"aiqofffixed" => Some(PlayerType::AIQOffFixed),
 		_ => None,
 	}
 }
+
+/// player type with settings, like minimax:depth=8:tt_memory=0. the settings are changed from the given ones
+fn parse_player(spec:&str, config:&Config) -> Result<PlayerSpec, CliError>
+{
+	let mut parts = spec.split(':');
+	let name = parts.next().unwrap_or("");
+	let ptype = string_to_player(&name.trim().to_lowercase())
+		.ok_or_else(|| CliError::Usage(format!("unknown player \"{}\"", name)))?;
+	let mut player = PlayerSpec::with_config(ptype, config.clone());
+	for setting in parts
+	{
+		let mut pair = setting.splitn(2, '=');
+		match (pair.next(), pair.next())
+		{
+			(Some(key), Some(value)) => player.set(key, value)?,
+			_ => return Err(CliError::Usage(format!("invalid player setting \"{}\", expected name=value", setting))),
+		}
+	}
+	Ok(player)
+}